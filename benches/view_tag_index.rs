@@ -0,0 +1,45 @@
+//! Compares the first scanning pass over a contiguous [`ViewTagIndex`] against the same filter
+//! over the announcements themselves.
+
+use ark_std::rand::{thread_rng, Rng};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use erc_5564_rs::announcement::Announcement;
+use erc_5564_rs::stealth_commitments::StealthAddressOnCurve;
+use erc_5564_rs::view_tag::ViewTagIndex;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "bls12_381")] {
+        type Curve = ark_bls12_381::Bls12_381;
+    } else if #[cfg(feature = "bls12_377")] {
+        type Curve = ark_bls12_377::Bls12_377;
+    } else if #[cfg(feature = "bn254")] {
+        type Curve = ark_bn254::Bn254;
+    }
+}
+
+fn view_tag_filter(c: &mut Criterion) {
+    let (_, public_key) = Curve::random_keypair();
+    let mut rng = thread_rng();
+    let announcements: Vec<Announcement<Curve>> = (0..100_000)
+        .map(|_| Announcement::new(public_key, public_key, rng.gen()))
+        .collect();
+    let index = ViewTagIndex::new(&announcements);
+    let expected: u8 = rng.gen();
+
+    c.bench_function("announcements filter", |b| {
+        b.iter(|| {
+            announcements
+                .iter()
+                .enumerate()
+                .filter(|(_, announcement)| announcement.view_tag as u8 == black_box(expected))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("view tag index candidates", |b| {
+        b.iter(|| index.candidates(black_box(expected)))
+    });
+}
+
+criterion_group!(benches, view_tag_filter);
+criterion_main!(benches);
@@ -0,0 +1,706 @@
+use crate::error::StealthError;
+use crate::meta_address::StealthMetaAddress;
+use crate::scheme::hash_to_fr_with_domain;
+use crate::scheme_registry;
+use crate::serialization::{
+    deserialize_public_key, public_key_size, serialize_fr, serialize_point,
+};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use crate::view_tag::{is_valid_view_tag_width, view_tag_mask, VIEW_TAG_BYTE_WIDTH};
+use ark_std::rand::Rng;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// The public data a sender publishes so the recipient can find their stealth commitment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement<C: StealthAddressOnCurve> {
+    pub stealth_commitment: C::Projective,
+    pub ephemeral_public_key: C::Projective,
+    pub view_tag: u64,
+    /// Free-form ERC-5564 metadata published alongside the announcement, empty by default. It is
+    /// carried by the JSON and EVM log encodings but not by [`Announcement::to_bytes`].
+    pub metadata: Vec<u8>,
+    /// Number of low view tag bytes the sender committed to, [`VIEW_TAG_BYTE_WIDTH`] by default.
+    /// Every encoding carries it, and recovery only compares these bytes of the tag.
+    pub view_tag_width: usize,
+    /// Block the announcement was emitted in, if known, for dropping matches after a reorg.
+    pub block_number: Option<u64>,
+}
+
+impl<C: StealthAddressOnCurve> Announcement<C> {
+    pub fn new(
+        stealth_commitment: C::Projective,
+        ephemeral_public_key: C::Projective,
+        view_tag: u64,
+    ) -> Self {
+        Announcement {
+            stealth_commitment,
+            ephemeral_public_key,
+            view_tag,
+            metadata: Vec::new(),
+            view_tag_width: VIEW_TAG_BYTE_WIDTH,
+            block_number: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Vec<u8>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_block_number(mut self, block_number: u64) -> Self {
+        self.block_number = Some(block_number);
+        self
+    }
+
+    /// Truncates the view tag to its low `view_tag_width` bytes and records the width, so a
+    /// recipient scanning with another width is told so by
+    /// [`crate::scan::recover_with_tag_width`] instead of silently missing the announcement.
+    pub fn with_view_tag_width(mut self, view_tag_width: usize) -> Self {
+        self.view_tag &= view_tag_mask(view_tag_width);
+        self.view_tag_width = view_tag_width;
+        self
+    }
+
+    /// Whether `view_tag`, derived from a shared secret, agrees with the announced tag in its low
+    /// `view_tag_width` bytes. An announcement with a width outside `1..=VIEW_TAG_BYTE_WIDTH`
+    /// matches no tag.
+    pub fn matches_view_tag(&self, view_tag: u64) -> bool {
+        let mask = view_tag_mask(self.view_tag_width);
+        is_valid_view_tag_width(self.view_tag_width) && view_tag & mask == self.view_tag & mask
+    }
+
+    /// Size in bytes of the encoding produced by [`Announcement::to_bytes`].
+    pub fn encoded_size() -> usize {
+        1 + 2 * public_key_size::<C::Projective>() + 1 + VIEW_TAG_BYTE_WIDTH
+    }
+
+    /// Encodes as `scheme_id || stealth_commitment || ephemeral_public_key || view_tag_width ||
+    /// view_tag`, with compressed points, a one byte width and a little-endian view tag. Identity
+    /// points are encoded as is, but [`Announcement::from_bytes`] rejects them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::encoded_size());
+        bytes.push(C::SCHEME.id());
+        bytes.extend(serialize_point(&self.stealth_commitment));
+        bytes.extend(serialize_point(&self.ephemeral_public_key));
+        bytes.push(self.view_tag_width as u8);
+        bytes.extend(self.view_tag.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes an announcement produced by [`Announcement::to_bytes`]. The leading scheme id is
+    /// resolved through the [`scheme_registry`] and must name the curve `C`. A width outside
+    /// `1..=VIEW_TAG_BYTE_WIDTH`, or a view tag with bytes set above it, is rejected with
+    /// [`StealthError::InvalidEncoding`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StealthError> {
+        let scheme_id = *bytes.first().ok_or(StealthError::InvalidLength {
+            expected: Self::encoded_size(),
+            actual: 0,
+        })?;
+        if scheme_registry::lookup(scheme_id)? != C::SCHEME {
+            return Err(StealthError::UnsupportedScheme(scheme_id));
+        }
+        if bytes.len() != Self::encoded_size() {
+            return Err(StealthError::InvalidLength {
+                expected: Self::encoded_size(),
+                actual: bytes.len(),
+            });
+        }
+
+        let point_size = public_key_size::<C::Projective>();
+        let (stealth_commitment, rest) = bytes[1..].split_at(point_size);
+        let (ephemeral_public_key, rest) = rest.split_at(point_size);
+        let view_tag_width = usize::from(rest[0]);
+        let view_tag = u64::from_le_bytes(rest[1..].try_into().unwrap());
+        if !is_valid_view_tag_width(view_tag_width)
+            || view_tag & !view_tag_mask(view_tag_width) != 0
+        {
+            return Err(StealthError::InvalidEncoding);
+        }
+        Ok(Announcement {
+            stealth_commitment: deserialize_public_key(stealth_commitment)?,
+            ephemeral_public_key: deserialize_public_key(ephemeral_public_key)?,
+            view_tag,
+            metadata: Vec::new(),
+            view_tag_width,
+            block_number: None,
+        })
+    }
+
+    /// Whether this announcement was made with the given ephemeral private key, for senders
+    /// reconciling announcements against the ephemeral keys they stored.
+    pub fn has_ephemeral_key(&self, ephemeral_private_key: C::Fr) -> bool {
+        ephemeral_public_key::<C>(ephemeral_private_key) == self.ephemeral_public_key
+    }
+}
+
+/// Version byte leading the output of [`serialize_announcements`]. Bump it on any layout change.
+pub const ANNOUNCEMENTS_FORMAT_VERSION: u8 = 2;
+
+/// Encodes a batch of announcements as `version || announcement || announcement || ...`, each
+/// announcement as in [`Announcement::to_bytes`].
+pub fn serialize_announcements<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(1 + announcements.len() * Announcement::<C>::encoded_size());
+    bytes.push(ANNOUNCEMENTS_FORMAT_VERSION);
+    for announcement in announcements {
+        bytes.extend(announcement.to_bytes());
+    }
+    bytes
+}
+
+/// Decodes a batch produced by [`serialize_announcements`], rejecting unknown format versions.
+/// There is no declared count: the number of records follows from the buffer length, which must
+/// be whole records, so a hostile buffer cannot make this allocate more than its own size.
+pub fn deserialize_announcements<C: StealthAddressOnCurve>(
+    bytes: &[u8],
+) -> Result<Vec<Announcement<C>>, StealthError> {
+    let (version, announcements) = bytes.split_first().ok_or(StealthError::InvalidLength {
+        expected: 1,
+        actual: 0,
+    })?;
+    if *version != ANNOUNCEMENTS_FORMAT_VERSION {
+        return Err(StealthError::UnsupportedVersion(*version));
+    }
+    let size = Announcement::<C>::encoded_size();
+    if announcements.len() % size != 0 {
+        return Err(StealthError::InvalidLength {
+            expected: announcements.len().next_multiple_of(size),
+            actual: announcements.len(),
+        });
+    }
+    announcements
+        .chunks_exact(size)
+        .map(Announcement::from_bytes)
+        .collect()
+}
+
+/// The ephemeral public key a sender publishes for a stored ephemeral private key.
+pub fn ephemeral_public_key<C: StealthAddressOnCurve>(
+    ephemeral_private_key: C::Fr,
+) -> C::Projective {
+    C::derive_public_key(&ephemeral_private_key)
+}
+
+/// Orders by the canonical encoding, [`Announcement::to_bytes`] followed by the metadata, so
+/// the order is the same on every machine whatever the projective representation of the points.
+impl<C: StealthAddressOnCurve + Eq> Ord for Announcement<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_bytes()
+            .cmp(&other.to_bytes())
+            .then_with(|| self.metadata.cmp(&other.metadata))
+            .then_with(|| self.view_tag_width.cmp(&other.view_tag_width))
+            .then_with(|| self.block_number.cmp(&other.block_number))
+    }
+}
+
+impl<C: StealthAddressOnCurve + Eq> PartialOrd for Announcement<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub fn generate_announcement<C: StealthAddressOnCurve>(
+    viewing_public_key: C::Projective,
+    spending_public_key: C::Projective,
+    ephemeral_private_key: C::Fr,
+) -> Result<Announcement<C>, StealthError> {
+    let (stealth_commitment, view_tag) = C::generate_stealth_commitment(
+        viewing_public_key,
+        spending_public_key,
+        ephemeral_private_key,
+    )?;
+    Ok(Announcement::new(
+        stealth_commitment,
+        ephemeral_public_key::<C>(ephemeral_private_key),
+        view_tag,
+    ))
+}
+
+/// Announces to `meta` with an ephemeral key derived from `label` and the sender's secret, so
+/// the same invoice or label always yields the same stealth address while the ephemeral key stays
+/// unknown to anyone without `sender_secret`.
+pub fn announce_for_label<C: StealthAddressOnCurve>(
+    meta: &StealthMetaAddress<C>,
+    label: &[u8],
+    sender_secret: C::Fr,
+) -> Result<Announcement<C>, StealthError> {
+    // the secret is fixed size, so label || secret is unambiguous
+    let mut input = label.to_vec();
+    input.extend(serialize_fr(&sender_secret));
+    generate_announcement(
+        meta.viewing_public_key,
+        meta.spending_public_key,
+        hash_to_fr_with_domain::<C>(b"label", &input),
+    )
+}
+
+/// Announces `count` payments to `meta` from a single stored ephemeral key, the `i`th using
+/// `base_ephemeral + hash_to_fr_with_domain("batch", base_ephemeral || i)`.
+///
+/// Plain multiples `i * e` would be unsafe: anyone can double or add the published ephemeral
+/// public keys to link the announcements, and leaking one ephemeral key leaks them all. The
+/// offsets here depend on the secret base, so the ephemeral public keys look independent to
+/// anyone without it. Offsets from the index alone would not do, since anyone could subtract the
+/// public `hash(i) * G` and find the common base.
+pub fn announce_batch_same_recipient<C: StealthAddressOnCurve>(
+    meta: &StealthMetaAddress<C>,
+    base_ephemeral: C::Fr,
+    count: usize,
+) -> Result<Vec<Announcement<C>>, StealthError> {
+    let base_bytes = serialize_fr(&base_ephemeral);
+    (0..count as u64)
+        .map(|index| {
+            let mut input = base_bytes.clone();
+            input.extend(index.to_le_bytes());
+            generate_announcement(
+                meta.viewing_public_key,
+                meta.spending_public_key,
+                base_ephemeral + hash_to_fr_with_domain::<C>(b"batch", &input),
+            )
+        })
+        .collect()
+}
+
+/// Tries up to `max_tries` random ephemeral keys for one whose announcement's first view tag
+/// byte, the one [`crate::view_tag::ViewTagIndex`] filters on, is `target_tag`. Returns the
+/// ephemeral private key and its announcement.
+pub fn search_ephemeral_for_tag<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    viewing_public_key: C::Projective,
+    spending_public_key: C::Projective,
+    target_tag: u8,
+    max_tries: usize,
+    rng: &mut R,
+) -> Option<(C::Fr, Announcement<C>)> {
+    (0..max_tries).find_map(|_| {
+        let ephemeral_private_key = C::generate_random_fr_with(rng);
+        let announcement = generate_announcement(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .ok()?;
+        (announcement.view_tag.to_le_bytes()[0] == target_tag)
+            .then_some((ephemeral_private_key, announcement))
+    })
+}
+
+/// Announces to `meta` with fresh ephemeral keys until the first view tag byte is not in
+/// `forbidden_tags`, e.g. to keep clear of tags a busy recipient already scans for.
+///
+/// # Panics
+///
+/// Panics if all 256 tag bytes are forbidden.
+pub fn announce_avoiding_tag<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    meta: &StealthMetaAddress<C>,
+    forbidden_tags: &HashSet<u8>,
+    rng: &mut R,
+) -> Result<Announcement<C>, StealthError> {
+    assert!(
+        forbidden_tags.len() < 256,
+        "at least one view tag byte must be allowed"
+    );
+    loop {
+        let announcement = generate_announcement(
+            meta.viewing_public_key,
+            meta.spending_public_key,
+            C::generate_random_fr_with(rng),
+        )?;
+        if !forbidden_tags.contains(&announcement.view_tag.to_le_bytes()[0]) {
+            return Ok(announcement);
+        }
+    }
+}
+
+/// Lazily announces to `meta`, with a fresh ephemeral key from `rng` for every item, for senders
+/// pushing announcements into a queue as they go. The stream ends early only if an announcement
+/// cannot be made, e.g. because `meta` holds an invalid key.
+pub fn announcement_stream<C: StealthAddressOnCurve, R: Rng>(
+    meta: StealthMetaAddress<C>,
+    mut rng: R,
+) -> impl Iterator<Item = Announcement<C>> {
+    std::iter::from_fn(move || {
+        generate_announcement(
+            meta.viewing_public_key,
+            meta.spending_public_key,
+            C::generate_random_fr_with(&mut rng),
+        )
+        .ok()
+    })
+}
+
+/// Checks, given the ephemeral private key, that `announcement` was correctly formed for `meta`:
+/// its ephemeral public key, stealth commitment and view tag must all be the ones derived from
+/// that key. For auditors resolving disputes over who an announcement paid.
+pub fn audit_announcement<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    ephemeral_private_key: C::Fr,
+    meta: &StealthMetaAddress<C>,
+) -> bool {
+    match generate_announcement::<C>(
+        meta.viewing_public_key,
+        meta.spending_public_key,
+        ephemeral_private_key,
+    ) {
+        Ok(expected) => {
+            expected.stealth_commitment == announcement.stealth_commitment
+                && expected.ephemeral_public_key == announcement.ephemeral_public_key
+                && expected.view_tag == announcement.view_tag
+        }
+        Err(_) => false,
+    }
+}
+
+/// Announces a payment to `recipient` and a change output back to the sender's own
+/// `self_meta`, each with a fresh ephemeral key so the two cannot be linked. Returns
+/// `(payment, change)`.
+pub fn announce_with_change<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    recipient: &StealthMetaAddress<C>,
+    self_meta: &StealthMetaAddress<C>,
+    rng: &mut R,
+) -> Result<(Announcement<C>, Announcement<C>), StealthError> {
+    let payment = generate_announcement(
+        recipient.viewing_public_key,
+        recipient.spending_public_key,
+        C::generate_random_fr_with(rng),
+    )?;
+    let change = generate_announcement(
+        self_meta.viewing_public_key,
+        self_meta.spending_public_key,
+        C::generate_random_fr_with(rng),
+    )?;
+    Ok((payment, change))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::recover;
+    use crate::scheme_registry::Scheme;
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::SeedableRng;
+    use std::collections::BTreeSet;
+
+    type Curve = crate::TestCurve;
+
+    fn random_announcement() -> Announcement<Curve> {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        generate_announcement(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_announcement_bytes_round_trip() {
+        let announcement = random_announcement();
+        let bytes = announcement.to_bytes();
+        assert_eq!(bytes.len(), Announcement::<Curve>::encoded_size());
+        assert_eq!(bytes[0], Curve::SCHEME.id());
+        assert_eq!(Announcement::from_bytes(&bytes), Ok(announcement));
+    }
+
+    #[test]
+    fn test_announcement_bytes_carry_view_tag_width() {
+        let announcement = random_announcement().with_view_tag_width(2);
+        let decoded = Announcement::<Curve>::from_bytes(&announcement.to_bytes()).unwrap();
+        assert_eq!(decoded.view_tag_width, 2);
+        assert_eq!(decoded, announcement);
+
+        let width_at = Announcement::<Curve>::encoded_size() - VIEW_TAG_BYTE_WIDTH - 1;
+        for width in [0, VIEW_TAG_BYTE_WIDTH as u8 + 1] {
+            let mut bytes = announcement.to_bytes();
+            bytes[width_at] = width;
+            assert_eq!(
+                Announcement::<Curve>::from_bytes(&bytes),
+                Err(StealthError::InvalidEncoding)
+            );
+        }
+        // a tag with bytes above the announced width
+        let mut bytes = announcement.to_bytes();
+        bytes[width_at + 1 + 2] = 1;
+        assert_eq!(
+            Announcement::<Curve>::from_bytes(&bytes),
+            Err(StealthError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn test_announcement_unsupported_scheme() {
+        let mut bytes = random_announcement().to_bytes();
+        let other = [Scheme::Bn254, Scheme::Bls12_381, Scheme::Bls12_377]
+            .into_iter()
+            .find(|scheme| *scheme != Curve::SCHEME)
+            .unwrap();
+        bytes[0] = other.id();
+        assert_eq!(
+            Announcement::<Curve>::from_bytes(&bytes),
+            Err(StealthError::UnsupportedScheme(other.id()))
+        );
+    }
+
+    #[test]
+    fn test_ephemeral_public_key_matches_announcement() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let (other_ephemeral_private_key, _) = Curve::random_keypair();
+
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert_eq!(
+            ephemeral_public_key::<Curve>(ephemeral_private_key),
+            announcement.ephemeral_public_key
+        );
+        assert!(announcement.has_ephemeral_key(ephemeral_private_key));
+        assert!(!announcement.has_ephemeral_key(other_ephemeral_private_key));
+    }
+
+    #[test]
+    fn test_announce_with_change() {
+        let (recipient_spending_key, recipient_spending_public_key) = Curve::random_keypair();
+        let (recipient_viewing_key, recipient_viewing_public_key) = Curve::random_keypair();
+        let (sender_spending_key, sender_spending_public_key) = Curve::random_keypair();
+        let (sender_viewing_key, sender_viewing_public_key) = Curve::random_keypair();
+        let recipient =
+            StealthMetaAddress::new(recipient_spending_public_key, recipient_viewing_public_key);
+        let self_meta =
+            StealthMetaAddress::new(sender_spending_public_key, sender_viewing_public_key);
+
+        let mut rng = StdRng::seed_from_u64(5564);
+        let (payment, change) =
+            announce_with_change::<Curve, _>(&recipient, &self_meta, &mut rng).unwrap();
+        assert_ne!(payment.ephemeral_public_key, change.ephemeral_public_key);
+
+        assert!(recover(&payment, recipient_viewing_key, recipient_spending_key).is_some());
+        assert!(recover(&change, sender_viewing_key, sender_spending_key).is_some());
+        assert!(recover(&payment, sender_viewing_key, sender_spending_key).is_none());
+        assert!(recover(&change, recipient_viewing_key, recipient_spending_key).is_none());
+    }
+
+    #[test]
+    fn test_announcement_ordering() {
+        let announcement = random_announcement();
+        // the same points in a different projective representation
+        let rescaled = Announcement::<Curve>::new(
+            announcement.stealth_commitment + announcement.stealth_commitment
+                - announcement.stealth_commitment,
+            announcement.ephemeral_public_key + announcement.ephemeral_public_key
+                - announcement.ephemeral_public_key,
+            announcement.view_tag,
+        );
+        assert_eq!(announcement.cmp(&rescaled), Ordering::Equal);
+        assert_eq!(BTreeSet::from([announcement.clone(), rescaled]).len(), 1);
+
+        let mut announcements: Vec<_> = (0..8).map(|_| random_announcement()).collect();
+        announcements.push(announcement.clone().with_metadata(vec![1]));
+        announcements.push(announcement);
+        let sorted: Vec<_> = BTreeSet::from_iter(announcements.iter().cloned())
+            .into_iter()
+            .collect();
+        announcements.sort();
+        assert_eq!(sorted, announcements);
+        assert!(announcements
+            .windows(2)
+            .all(|pair| (pair[0].to_bytes(), &pair[0].metadata)
+                < (pair[1].to_bytes(), &pair[1].metadata)));
+    }
+
+    #[test]
+    fn test_audit_announcement() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let (other_ephemeral_private_key, other_public_key) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        assert!(audit_announcement(
+            &announcement,
+            ephemeral_private_key,
+            &meta
+        ));
+        assert!(!audit_announcement(
+            &announcement,
+            other_ephemeral_private_key,
+            &meta
+        ));
+        assert!(!audit_announcement(
+            &announcement,
+            ephemeral_private_key,
+            &StealthMetaAddress::new(other_public_key, viewing_public_key)
+        ));
+
+        let mut tampered = announcement.clone();
+        tampered.view_tag ^= 1;
+        assert!(!audit_announcement(&tampered, ephemeral_private_key, &meta));
+        let mut tampered = announcement;
+        tampered.stealth_commitment = other_public_key;
+        assert!(!audit_announcement(&tampered, ephemeral_private_key, &meta));
+    }
+
+    #[test]
+    fn test_announce_for_label() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (sender_secret, _) = Curve::random_keypair();
+        let (other_sender_secret, _) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+
+        let invoice = announce_for_label(&meta, b"invoice-1", sender_secret).unwrap();
+        assert_eq!(
+            announce_for_label(&meta, b"invoice-1", sender_secret).unwrap(),
+            invoice
+        );
+        assert_ne!(
+            announce_for_label(&meta, b"invoice-2", sender_secret).unwrap(),
+            invoice
+        );
+        assert_ne!(
+            announce_for_label(&meta, b"invoice-1", other_sender_secret).unwrap(),
+            invoice
+        );
+        assert!(recover(&invoice, viewing_key, spending_key).is_some());
+    }
+
+    #[test]
+    fn test_announcements_format_version() {
+        let (_, public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announcements = vec![
+            Announcement::<Curve>::new(public_key, other_public_key, 1),
+            Announcement::<Curve>::new(other_public_key, public_key, 2),
+        ];
+
+        let bytes = serialize_announcements(&announcements);
+        assert_eq!(bytes[0], ANNOUNCEMENTS_FORMAT_VERSION);
+        assert_eq!(
+            deserialize_announcements::<Curve>(&bytes),
+            Ok(announcements)
+        );
+        assert_eq!(
+            deserialize_announcements::<Curve>(&serialize_announcements::<Curve>(&[])),
+            Ok(vec![])
+        );
+
+        let mut bumped = bytes.clone();
+        bumped[0] += 1;
+        assert_eq!(
+            deserialize_announcements::<Curve>(&bumped),
+            Err(StealthError::UnsupportedVersion(
+                ANNOUNCEMENTS_FORMAT_VERSION + 1
+            ))
+        );
+        assert!(deserialize_announcements::<Curve>(&[]).is_err());
+        assert!(deserialize_announcements::<Curve>(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_announce_batch_same_recipient() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (base_ephemeral, _) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+
+        let announcements = announce_batch_same_recipient(&meta, base_ephemeral, 8).unwrap();
+        assert_eq!(announcements.len(), 8);
+        for announcement in &announcements {
+            assert!(recover(announcement, viewing_key, spending_key).is_some());
+        }
+        let ephemeral_keys: BTreeSet<_> = announcements
+            .iter()
+            .map(|announcement| serialize_point(&announcement.ephemeral_public_key))
+            .collect();
+        assert_eq!(ephemeral_keys.len(), 8);
+        assert!(
+            !ephemeral_keys.contains(&serialize_point(&Curve::derive_public_key(&base_ephemeral)))
+        );
+        assert_eq!(
+            announce_batch_same_recipient(&meta, base_ephemeral, 8).unwrap(),
+            announcements
+        );
+    }
+
+    #[test]
+    fn test_search_ephemeral_for_tag() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let mut rng = StdRng::seed_from_u64(5564);
+
+        let (ephemeral_private_key, announcement) = search_ephemeral_for_tag::<Curve, _>(
+            viewing_public_key,
+            spending_public_key,
+            0x42,
+            10_000,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(announcement.view_tag as u8, 0x42);
+        assert!(announcement.has_ephemeral_key(ephemeral_private_key));
+        assert!(recover(&announcement, viewing_key, spending_key).is_some());
+
+        assert!(search_ephemeral_for_tag::<Curve, _>(
+            viewing_public_key,
+            spending_public_key,
+            0x42,
+            0,
+            &mut rng
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_announce_avoiding_tag() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+        let mut rng = StdRng::seed_from_u64(5564);
+
+        // all but two tag bytes, so a plain announcement would almost always collide
+        let forbidden: HashSet<u8> = (0..=255).filter(|tag| *tag != 7 && *tag != 200).collect();
+        for _ in 0..4 {
+            let announcement = announce_avoiding_tag(&meta, &forbidden, &mut rng).unwrap();
+            assert!(!forbidden.contains(&(announcement.view_tag as u8)));
+            assert!(recover(&announcement, viewing_key, spending_key).is_some());
+        }
+    }
+
+    #[test]
+    fn test_announcement_stream() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+
+        let announcements: Vec<_> = announcement_stream(meta, StdRng::seed_from_u64(5564))
+            .take(10)
+            .collect();
+        assert_eq!(announcements.len(), 10);
+        let ephemeral_keys: BTreeSet<_> = announcements
+            .iter()
+            .map(|announcement| serialize_point(&announcement.ephemeral_public_key))
+            .collect();
+        let commitments: BTreeSet<_> = announcements
+            .iter()
+            .map(|announcement| serialize_point(&announcement.stealth_commitment))
+            .collect();
+        assert_eq!(ephemeral_keys.len(), 10);
+        assert_eq!(commitments.len(), 10);
+        for announcement in &announcements {
+            assert!(recover(announcement, viewing_key, spending_key).is_some());
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::announcement::Announcement;
+use crate::scan::recover;
+use crate::stealth_commitments::StealthAddressOnCurve;
+use futures::executor::ThreadPool;
+use futures::future;
+use futures::stream::{Stream, StreamExt};
+use futures::task::SpawnExt;
+
+/// Yields every announcement from `stream` that is addressed to these keys, along with its
+/// stealth private key. Recovery runs inline on the polling task.
+pub fn scan_stream<C, S>(
+    stream: S,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> impl Stream<Item = (Announcement<C>, C::Fr)>
+where
+    C: StealthAddressOnCurve,
+    S: Stream<Item = Announcement<C>>,
+{
+    stream.filter_map(move |announcement| {
+        let matched = recover(&announcement, viewing_key, spending_key)
+            .map(|stealth_private_key| (announcement, stealth_private_key));
+        future::ready(matched)
+    })
+}
+
+/// Like [`scan_stream`], but runs each recovery on `pool` so the polling task never does the
+/// scalar multiplications itself. Output order follows the input stream.
+pub fn scan_stream_on_pool<C, S>(
+    stream: S,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    pool: ThreadPool,
+) -> impl Stream<Item = (Announcement<C>, C::Fr)>
+where
+    C: StealthAddressOnCurve + 'static,
+    S: Stream<Item = Announcement<C>>,
+{
+    stream
+        .then(move |announcement| {
+            pool.spawn_with_handle(async move {
+                recover(&announcement, viewing_key, spending_key)
+                    .map(|stealth_private_key| (announcement, stealth_private_key))
+            })
+            .expect("thread pool is running")
+        })
+        .filter_map(future::ready)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+    use crate::scan::scan_announcements;
+    use futures::executor::block_on;
+    use futures::stream;
+
+    type Curve = crate::TestCurve;
+
+    fn announcements(
+        viewing_public_key: <Curve as StealthAddressOnCurve>::Projective,
+        spending_public_key: <Curve as StealthAddressOnCurve>::Projective,
+    ) -> Vec<Announcement<Curve>> {
+        let (_, other_spending_public_key) = Curve::random_keypair();
+        let (_, other_viewing_public_key) = Curve::random_keypair();
+        (0..6)
+            .map(|i| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if i % 2 == 0 {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                } else {
+                    generate_announcement(
+                        other_viewing_public_key,
+                        other_spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_stream() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let announcements = announcements(viewing_public_key, spending_public_key);
+
+        let expected: Vec<_> = scan_announcements(&announcements, viewing_key, spending_key)
+            .into_iter()
+            .map(|result| {
+                (
+                    announcements[result.index].clone(),
+                    result.stealth_private_key,
+                )
+            })
+            .collect();
+        assert_eq!(expected.len(), 3);
+
+        let inline = block_on(
+            scan_stream(
+                stream::iter(announcements.clone()),
+                viewing_key,
+                spending_key,
+            )
+            .collect::<Vec<_>>(),
+        );
+        assert_eq!(inline, expected);
+
+        let pool = ThreadPool::new().unwrap();
+        let offloaded = block_on(
+            scan_stream_on_pool(stream::iter(announcements), viewing_key, spending_key, pool)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(offloaded, expected);
+    }
+}
@@ -0,0 +1,268 @@
+//! Batched key derivation and shared point computation.
+//!
+//! With the `msm` feature, [`derive_public_keys`] multiplies the generator through a fixed-base
+//! window table and [`compute_shared_points_batch`] reuses one wNAF decomposition of the private
+//! key across every public key. Without it both fall back to one scalar multiplication per
+//! input. The outputs are identical either way.
+
+use crate::stealth_commitments::{shared_secret_hash_preimage, StealthAddressOnCurve};
+use ark_ec::{AffineRepr, CurveGroup};
+
+/// The public keys of `private_keys`, in order.
+pub fn derive_public_keys<C: StealthAddressOnCurve>(private_keys: &[C::Fr]) -> Vec<C::Projective> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "msm")] {
+            msm::derive_public_keys::<C>(private_keys)
+        } else {
+            derive_public_keys_naive::<C>(private_keys)
+        }
+    }
+}
+
+/// The shared points of `private_key` with each of `public_keys`, in order.
+pub fn compute_shared_points_batch<C: StealthAddressOnCurve>(
+    private_key: C::Fr,
+    public_keys: &[C::Projective],
+) -> Vec<C::Projective> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "msm")] {
+            msm::compute_shared_points_batch::<C>(private_key, public_keys)
+        } else {
+            compute_shared_points_batch_naive::<C>(private_key, public_keys)
+        }
+    }
+}
+
+/// The affine forms of `points`, in order, normalized together with a single field inversion
+/// instead of one per point.
+pub fn normalize_batch<C: StealthAddressOnCurve>(
+    points: &[C::Projective],
+) -> Vec<<C::Projective as CurveGroup>::Affine> {
+    C::Projective::normalize_batch(points)
+}
+
+/// The stealth addresses a watch-only wallet expects for each of `ephemeral_public_keys`, in
+/// order, from the viewing key and the public spending key alone. The shared points, their
+/// normalization and the hashed secrets' public keys are each computed as one batch. An
+/// ephemeral public key whose shared point is the identity, which only malformed data produces,
+/// expects no address and yields `None`.
+pub fn expected_addresses<C: StealthAddressOnCurve>(
+    ephemeral_public_keys: &[C::Projective],
+    viewing_key: C::Fr,
+    spending_public_key: C::Projective,
+) -> Vec<Option<C::Projective>> {
+    let shared_points = normalize_batch::<C>(&compute_shared_points_batch::<C>(
+        viewing_key,
+        ephemeral_public_keys,
+    ));
+    let shared_secrets: Vec<_> = shared_points
+        .iter()
+        .filter(|shared_point| !shared_point.is_zero())
+        .map(|shared_point| C::hash_to_fr(&shared_secret_hash_preimage(shared_point)))
+        .collect();
+    let mut public_keys = derive_public_keys::<C>(&shared_secrets).into_iter();
+    shared_points
+        .iter()
+        .map(|shared_point| {
+            (!shared_point.is_zero())
+                .then(|| {
+                    public_keys
+                        .next()
+                        .expect("one public key per shared secret")
+                })
+                .map(|public_key| public_key + spending_public_key)
+        })
+        .collect()
+}
+
+/// Lazily yields the public keys of `start, start + step, start + 2 * step, ...`, `count` of
+/// them, with one point addition per key after the first instead of a scalar multiplication.
+pub fn public_key_range<C: StealthAddressOnCurve>(
+    start: C::Fr,
+    step: C::Fr,
+    count: usize,
+) -> impl Iterator<Item = C::Projective> {
+    let step = C::derive_public_key(&step);
+    std::iter::successors(Some(C::derive_public_key(&start)), move |public_key| {
+        Some(*public_key + step)
+    })
+    .take(count)
+}
+
+#[cfg(any(test, not(feature = "msm")))]
+fn derive_public_keys_naive<C: StealthAddressOnCurve>(
+    private_keys: &[C::Fr],
+) -> Vec<C::Projective> {
+    private_keys.iter().map(C::derive_public_key).collect()
+}
+
+#[cfg(any(test, not(feature = "msm")))]
+fn compute_shared_points_batch_naive<C: StealthAddressOnCurve>(
+    private_key: C::Fr,
+    public_keys: &[C::Projective],
+) -> Vec<C::Projective> {
+    public_keys
+        .iter()
+        .map(|public_key| C::compute_shared_point(private_key, *public_key))
+        .collect()
+}
+
+#[cfg(feature = "msm")]
+mod msm {
+    use crate::stealth_commitments::StealthAddressOnCurve;
+    use ark_ec::scalar_mul::fixed_base::FixedBase;
+    use ark_ec::Group;
+    use ark_ff::{BigInteger, PrimeField};
+    use num_traits::{One, Zero};
+
+    const WNAF_WINDOW: usize = 4;
+
+    pub(super) fn derive_public_keys<C: StealthAddressOnCurve>(
+        private_keys: &[C::Fr],
+    ) -> Vec<C::Projective> {
+        let scalar_size = C::Fr::MODULUS_BIT_SIZE as usize;
+        let window = FixedBase::get_mul_window_size(private_keys.len());
+        let generator = C::derive_public_key(&C::Fr::one());
+        let table = FixedBase::get_window_table(scalar_size, window, generator);
+        FixedBase::msm(scalar_size, window, &table, private_keys)
+    }
+
+    pub(super) fn compute_shared_points_batch<C: StealthAddressOnCurve>(
+        private_key: C::Fr,
+        public_keys: &[C::Projective],
+    ) -> Vec<C::Projective> {
+        let wnaf = private_key
+            .into_bigint()
+            .find_wnaf(WNAF_WINDOW)
+            .expect("the wNAF window is a constant in range");
+        public_keys
+            .iter()
+            .map(|public_key| wnaf_mul::<C>(*public_key, &wnaf))
+            .collect()
+    }
+
+    fn wnaf_mul<C: StealthAddressOnCurve>(base: C::Projective, wnaf: &[i64]) -> C::Projective {
+        // odd multiples base, 3 * base, 5 * base, ...
+        let double = base.double();
+        let mut odd_multiples = vec![base];
+        for _ in 1..1 << (WNAF_WINDOW - 1) {
+            let next = *odd_multiples.last().expect("starts non-empty") + double;
+            odd_multiples.push(next);
+        }
+
+        let mut result = C::Projective::zero();
+        for digit in wnaf.iter().rev() {
+            result.double_in_place();
+            if *digit > 0 {
+                result += odd_multiples[(*digit / 2) as usize];
+            } else if *digit < 0 {
+                result -= odd_multiples[(-*digit / 2) as usize];
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::Zero;
+
+    type Curve = crate::TestCurve;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    #[test]
+    fn test_batch_matches_naive() {
+        let private_keys: Vec<Fr> = [0u64, 1, 2, u64::MAX]
+            .into_iter()
+            .map(Fr::from)
+            .chain((0..40).map(|_| Curve::random_keypair().0))
+            .chain([-Fr::from(1u64)])
+            .collect();
+        let public_keys = derive_public_keys::<Curve>(&private_keys);
+        assert_eq!(
+            public_keys,
+            derive_public_keys_naive::<Curve>(&private_keys)
+        );
+
+        for private_key in [Fr::from(0u64), Fr::from(1u64), -Fr::from(1u64)]
+            .into_iter()
+            .chain((0..4).map(|_| Curve::random_keypair().0))
+        {
+            assert_eq!(
+                compute_shared_points_batch::<Curve>(private_key, &public_keys),
+                compute_shared_points_batch_naive::<Curve>(private_key, &public_keys)
+            );
+        }
+        assert!(derive_public_keys::<Curve>(&[]).is_empty());
+        assert!(compute_shared_points_batch::<Curve>(Fr::from(2u64), &[]).is_empty());
+    }
+
+    #[test]
+    fn test_public_key_range_matches_naive() {
+        let (start, _) = Curve::random_keypair();
+        let (step, _) = Curve::random_keypair();
+        let keys: Vec<_> = public_key_range::<Curve>(start, step, 10).collect();
+        let naive: Vec<_> = (0..10u64)
+            .map(|i| Curve::derive_public_key(&(start + Fr::from(i) * step)))
+            .collect();
+        assert_eq!(keys, naive);
+        assert_eq!(public_key_range::<Curve>(start, step, 0).count(), 0);
+    }
+
+    #[test]
+    fn test_normalize_batch_matches_into_affine() {
+        let points: Vec<_> = (0..10)
+            .map(|_| Curve::random_keypair().1)
+            .chain([Curve::derive_public_key(&Fr::from(0u64))])
+            .collect();
+        let affine: Vec<_> = points.iter().map(|point| point.into_affine()).collect();
+        assert_eq!(normalize_batch::<Curve>(&points), affine);
+        assert!(normalize_batch::<Curve>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_expected_addresses_match_sender() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let ephemeral_private_keys: Vec<Fr> = (0..10).map(|_| Curve::random_keypair().0).collect();
+        let ephemeral_public_keys = derive_public_keys::<Curve>(&ephemeral_private_keys);
+
+        let addresses =
+            expected_addresses::<Curve>(&ephemeral_public_keys, viewing_key, spending_public_key);
+        assert_eq!(addresses.len(), ephemeral_private_keys.len());
+        for (address, ephemeral_private_key) in addresses.iter().zip(ephemeral_private_keys) {
+            let (stealth_commitment, _) = Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+            assert_eq!(*address, Some(stealth_commitment));
+        }
+        assert!(expected_addresses::<Curve>(&[], viewing_key, spending_public_key).is_empty());
+    }
+
+    #[test]
+    fn test_expected_addresses_skip_degenerate_shared_points() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let identity = <Curve as StealthAddressOnCurve>::Projective::zero();
+
+        let (stealth_commitment, _) = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert_eq!(
+            expected_addresses::<Curve>(
+                &[identity, ephemeral_public_key, identity],
+                viewing_key,
+                spending_public_key
+            ),
+            [None, Some(stealth_commitment), None]
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::scheme::{hash_to_fr_with_domain, EMPTY_INPUT_DOMAIN};
+use crate::scheme_registry::Scheme;
 use crate::stealth_commitments::{AffineWrapper, RawFr, StealthAddressOnCurve};
 use ark_bls12_381::g1::{G1_GENERATOR_X, G1_GENERATOR_Y};
 use ark_bls12_381::{Fq, Fr, G1Affine, G1Projective};
@@ -31,12 +33,21 @@ impl StealthAddressOnCurve for ark_bls12_381::Bls12_381 {
     type Affine = Bls12_381_G1Affine;
     type Fr = Fr;
 
+    const SCHEME: Scheme = Scheme::Bls12_381;
+
     fn derive_public_key(private_key: &Self::Fr) -> Self::Projective {
         let g1_generator_affine = Self::Affine::new(G1_GENERATOR_X, G1_GENERATOR_Y);
         (Self::Projective::from(g1_generator_affine)) * *private_key
     }
 
     fn hash_to_fr(input: &[u8]) -> Self::Fr {
+        if input.is_empty() {
+            return hash_to_fr_with_domain::<Self>(EMPTY_INPUT_DOMAIN, &[]);
+        }
+        #[cfg(feature = "ffi")]
+        if let Some(hash) = crate::ffi::hash_backend_digest(input) {
+            return Self::Fr::from_le_bytes_mod_order(&hash);
+        }
         let mut hash = [0; 32];
         let mut hasher = Keccak::v256();
         hasher.update(input);
@@ -51,9 +62,15 @@ impl StealthAddressOnCurve for ark_bls12_381::Bls12_381 {
 mod tests {
     use super::*;
     use ark_ec::CurveGroup;
+    use std::str::FromStr;
 
     type Curve = ark_bls12_381::Bls12_381;
 
+    #[test]
+    fn test_validate_generator() {
+        assert_eq!(Curve::validate_generator(), Ok(()));
+    }
+
     #[test]
     fn test_random_keypair() {
         let (key, pub_key) = Curve::random_keypair();
@@ -99,7 +116,8 @@ mod tests {
             viewing_public_key,
             spending_public_key,
             ephemeral_private_key,
-        );
+        )
+        .unwrap();
 
         let stealth_private_key_opt = Curve::generate_stealth_private_key(
             ephemeral_public_key,
@@ -115,4 +133,36 @@ mod tests {
         let derived_commitment = Curve::derive_public_key(&stealth_private_key_opt.unwrap());
         assert_eq!(derived_commitment, stealth_commitment);
     }
+
+    #[test]
+    fn test_degenerate_ephemeral_key_rejected() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+
+        for ephemeral_private_key in [Fr::from(0u64), Fr::from(1u64)] {
+            assert_eq!(
+                Curve::generate_stealth_commitment(
+                    viewing_public_key,
+                    spending_public_key,
+                    ephemeral_private_key,
+                ),
+                Err(crate::error::StealthError::DegenerateEphemeralKey)
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_to_fr_empty_input_is_pinned() {
+        assert_eq!(
+            Curve::hash_to_fr(&[]),
+            Fr::from_str(
+                "47765325398411529666546785818768485652114147679748435853199462709021032011345"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Curve::hash_to_fr(&[]),
+            hash_to_fr_with_domain::<Curve>(EMPTY_INPUT_DOMAIN, &[])
+        );
+    }
 }
@@ -1,7 +1,20 @@
+use crate::announcement::Announcement;
+use crate::error::StealthError;
+use crate::scan::scan_announcements;
+use crate::scheme::{hash_to_fr_with_domain, EMPTY_INPUT_DOMAIN};
+use crate::scheme_registry::Scheme;
 use crate::stealth_commitments::{AffineWrapper, RawFr, StealthAddressOnCurve};
 use ark_bn254::g1::{G1_GENERATOR_X, G1_GENERATOR_Y};
-use ark_bn254::{Fq, Fr, G1Affine, G1Projective};
-use rln::hashers::{hash_to_field, poseidon_hash};
+use ark_bn254::{Bn254, Fq, Fr, G1Affine, G1Projective};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::CanonicalDeserialize;
+use num_traits::Zero;
+use rln::hashers::{hash_to_field, poseidon_hash, ROUND_PARAMS};
+use rln::protocol::prepare_prove_input;
+use rln::utils::fr_to_bytes_le;
+#[cfg(feature = "ffi")]
+use rln::utils::bytes_le_to_fr;
 
 impl AffineWrapper for G1Affine {
     type Fq = Fq;
@@ -17,35 +30,261 @@ impl RawFr for Fr {
     }
 }
 
-impl StealthAddressOnCurve for ark_bn254::Bn254 {
+impl StealthAddressOnCurve for Bn254 {
     type Projective = G1Projective;
     type Affine = G1Affine;
     type Fr = Fr;
 
+    const SCHEME: Scheme = Scheme::Bn254;
+
     fn derive_public_key(private_key: &Self::Fr) -> Self::Projective {
         let g1_generator_affine = Self::Affine::new(G1_GENERATOR_X, G1_GENERATOR_Y);
         (Self::Projective::from(g1_generator_affine)) * *private_key
     }
 
     fn hash_to_fr(input: &[u8]) -> Self::Fr {
+        if input.is_empty() {
+            return hash_to_fr_with_domain::<Self>(EMPTY_INPUT_DOMAIN, &[]);
+        }
+        #[cfg(feature = "ffi")]
+        if let Some(hash) = crate::ffi::hash_backend_digest(input) {
+            return poseidon_hash(&[bytes_le_to_fr(&hash).0]);
+        }
         poseidon_hash(&[hash_to_field(input)])
     }
+
+    fn hash_coordinates_to_fr(point: &Self::Projective) -> Self::Fr {
+        let point = point.into_affine();
+        let to_fr =
+            |coordinate: Fq| Fr::from_le_bytes_mod_order(&coordinate.into_bigint().to_bytes_le());
+        poseidon_hash(&[to_fr(point.x), to_fr(point.y)])
+    }
+
+    /// The 64 byte big-endian `X || Y` of [`deserialize_public_key_uncompressed`], as the EVM
+    /// precompiles and announcer contracts use.
+    fn deserialize_public_key_uncompressed(bytes: &[u8]) -> Result<Self::Projective, StealthError> {
+        let bytes: &[u8; 64] = bytes.try_into().map_err(|_| StealthError::InvalidLength {
+            expected: 64,
+            actual: bytes.len(),
+        })?;
+        deserialize_public_key_uncompressed(bytes)
+    }
+
+    /// `poseidon_hash([hash_to_field(domain), x, y])`.
+    fn hash_coordinates_to_fr_with_domain(domain: &[u8], point: &Self::Projective) -> Self::Fr {
+        let point = point.into_affine();
+        let to_fr =
+            |coordinate: Fq| Fr::from_le_bytes_mod_order(&coordinate.into_bigint().to_bytes_le());
+        poseidon_hash(&[hash_to_field(domain), to_fr(point.x), to_fr(point.y)])
+    }
+}
+
+/// The poseidon instance behind `poseidon_hash`, for integrators reimplementing
+/// [`StealthAddressOnCurve::hash_to_fr`] on-chain. This is circomlib's poseidon over the bn254
+/// scalar field with the x^5 s-box.
+///
+/// `hash_to_fr(input)` is `poseidon_hash([keccak256(input) as a little-endian Fr])`, i.e. a
+/// single field element input and width [`PoseidonParamsInfo::width`] = 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoseidonParamsInfo {
+    /// Number of field element inputs `hash_to_fr` passes to poseidon.
+    pub arity: usize,
+    /// State width `t`, the arity plus the capacity element.
+    pub width: usize,
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    pub sbox_exponent: u64,
+}
+
+pub fn poseidon_params() -> PoseidonParamsInfo {
+    let arity = 1;
+    let (width, full_rounds, partial_rounds, _) = ROUND_PARAMS
+        .into_iter()
+        .find(|(width, ..)| *width == arity + 1)
+        .expect("rln supports single element poseidon");
+    PoseidonParamsInfo {
+        arity,
+        width,
+        full_rounds,
+        partial_rounds,
+        sbox_exponent: 5,
+    }
+}
+
+/// Order-sensitive poseidon digest over the serialized announcements, so that two parties can
+/// cheaply confirm they hold the same announcement set.
+pub fn announcements_digest(anns: &[Announcement<Bn254>]) -> Fr {
+    anns.iter().fold(Fr::from(0), |acc, announcement| {
+        poseidon_hash(&[acc, hash_to_field(&announcement.to_bytes())])
+    })
+}
+
+/// The rln tree leaf for a stealth commitment: the poseidon hash of its affine coordinates, each
+/// reduced into the scalar field. The commitment is normalized first, so any projective
+/// representation of the same point gives the same leaf.
+pub fn commitment_to_rln_leaf(commitment: &G1Projective) -> Fr {
+    // the identity has no affine coordinates, and normalizes to a (0, 0) placeholder
+    debug_assert!(
+        !commitment.is_zero(),
+        "a stealth commitment is never the identity"
+    );
+    Bn254::hash_coordinates_to_fr(commitment)
+}
+
+/// Like [`scan_announcements`], but returns the rln leaf of each matched stealth commitment
+/// alongside the announcement index and stealth private key.
+pub fn scan_announcements_as_rln_leaves(
+    announcements: &[Announcement<Bn254>],
+    viewing_key: Fr,
+    spending_key: Fr,
+) -> Vec<(usize, Fr, Fr)> {
+    scan_announcements(announcements, viewing_key, spending_key)
+        .into_iter()
+        .map(|result| {
+            (
+                result.index,
+                result.stealth_private_key,
+                commitment_to_rln_leaf(&result.stealth_address),
+            )
+        })
+        .collect()
+}
+
+/// What rln needs to prove membership with a recovered stealth private key.
+///
+/// The stealth private key is used directly as the rln identity secret, so the tree leaf is
+/// `identity_commitment = poseidon_hash([stealth_private_key])`, which the rln circuit recomputes.
+/// This differs from [`commitment_to_rln_leaf`], which a sender can compute without the secret
+/// but which the rln circuit cannot prove against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RlnWitness {
+    pub identity_secret: Fr,
+    pub identity_commitment: Fr,
+    pub leaf_index: usize,
+}
+
+impl RlnWitness {
+    /// The serialized input for `RLN::generate_rln_proof`, signalling `signal` in `epoch`.
+    pub fn prove_input(&self, epoch: Fr, signal: &[u8]) -> Vec<u8> {
+        prepare_prove_input(self.identity_secret, self.leaf_index, epoch, signal)
+    }
+}
+
+pub fn stealth_rln_witness(stealth_private_key: Fr, leaf_index: usize) -> RlnWitness {
+    RlnWitness {
+        identity_secret: stealth_private_key,
+        identity_commitment: poseidon_hash(&[stealth_private_key]),
+        leaf_index,
+    }
+}
+
+/// The identity credential of a recovered stealth key, in the JSON layout of rln's `key_gen`
+/// output plus the tree index:
+///
+/// ```json
+/// {"identity_secret_hash":"0x…","id_commitment":"0x…","leaf_index":3}
+/// ```
+///
+/// Both field elements are the `0x` prefixed hex of their little-endian `fr_to_bytes_le`
+/// encoding, the one rln's public API reads.
+pub fn stealth_key_to_rln_credential_json(stealth_key: Fr, leaf_index: usize) -> String {
+    let witness = stealth_rln_witness(stealth_key, leaf_index);
+    format!(
+        r#"{{"identity_secret_hash":"0x{}","id_commitment":"0x{}","leaf_index":{}}}"#,
+        hex::encode(fr_to_bytes_le(&witness.identity_secret)),
+        hex::encode(fr_to_bytes_le(&witness.identity_commitment)),
+        witness.leaf_index
+    )
+}
+
+/// Encodes a point as the 64 bytes the EVM ecAdd/ecMul precompiles take: big-endian X then Y.
+/// The identity is encoded as all zeroes, as the precompiles do.
+pub fn serialize_public_key_uncompressed(point: &G1Projective) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    if let Some((x, y)) = point.into_affine().xy() {
+        bytes[..32].copy_from_slice(&x.into_bigint().to_bytes_be());
+        bytes[32..].copy_from_slice(&y.into_bigint().to_bytes_be());
+    }
+    bytes
+}
+
+/// Decodes the precompile encoding, rejecting non-canonical coordinates, points that are not on
+/// the curve, and the identity.
+pub fn deserialize_public_key_uncompressed(bytes: &[u8; 64]) -> Result<G1Projective, StealthError> {
+    let coordinate = |be: &[u8]| {
+        let mut le = be.to_vec();
+        le.reverse();
+        Fq::deserialize_compressed(le.as_slice())
+    };
+    let x = coordinate(&bytes[..32])?;
+    let y = coordinate(&bytes[32..])?;
+    if x.is_zero() && y.is_zero() {
+        return Err(StealthError::IdentityPoint);
+    }
+    let point = G1Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(StealthError::InvalidEncoding);
+    }
+    Ok(point.into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ark_ec::CurveGroup;
+    use crate::announcement::generate_announcement;
+    use crate::keypair::Keypair;
+    use crate::meta_address::StealthMetaAddress;
+    use crate::scan::{recover, recover_single_key};
     use ark_std::rand::thread_rng;
     use ark_std::UniformRand;
     use color_eyre::{Report, Result};
+    use num_traits::Zero;
     use rln::public::RLN;
-    use rln::utils::fr_to_bytes_le;
+    use rln::utils::bytes_le_to_fr;
     use serde_json::json;
     use std::io::Cursor;
+    use std::str::FromStr;
+    use tiny_keccak::{Hasher, Keccak};
 
     type Curve = ark_bn254::Bn254;
 
+    #[test]
+    fn test_validate_generator() {
+        assert_eq!(Curve::validate_generator(), Ok(()));
+    }
+
+    #[test]
+    fn test_poseidon_params() {
+        assert_eq!(
+            poseidon_params(),
+            PoseidonParamsInfo {
+                arity: 1,
+                width: 2,
+                full_rounds: 8,
+                partial_rounds: 56,
+                sbox_exponent: 5,
+            }
+        );
+
+        // circomlib's poseidon([1])
+        assert_eq!(
+            poseidon_hash(&[Fr::from(1u64)]),
+            Fr::from_str(
+                "18586133768512220936620570745912940619677854269274689475585506675881198879027"
+            )
+            .unwrap()
+        );
+
+        let mut keccak = [0u8; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(b"erc-5564");
+        hasher.finalize(&mut keccak);
+        assert_eq!(
+            Curve::hash_to_fr(b"erc-5564"),
+            poseidon_hash(&[Fr::from_le_bytes_mod_order(&keccak)])
+        );
+    }
+
     #[test]
     fn test_random_keypair() {
         let (key, pub_key) = Curve::random_keypair();
@@ -91,7 +330,8 @@ mod tests {
             viewing_public_key,
             spending_public_key,
             ephemeral_private_key,
-        );
+        )
+        .unwrap();
 
         let stealth_private_key_opt = Curve::generate_stealth_private_key(
             ephemeral_public_key,
@@ -108,6 +348,170 @@ mod tests {
         assert_eq!(derived_commitment, stealth_commitment);
     }
 
+    #[test]
+    fn test_degenerate_ephemeral_key_rejected() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+
+        for ephemeral_private_key in [Fr::from(0u64), Fr::from(1u64)] {
+            assert_eq!(
+                Curve::generate_stealth_commitment(
+                    viewing_public_key,
+                    spending_public_key,
+                    ephemeral_private_key,
+                ),
+                Err(crate::error::StealthError::DegenerateEphemeralKey)
+            );
+        }
+    }
+
+    #[test]
+    fn test_announcements_digest() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let announcements: Vec<Announcement<Curve>> = (0..3)
+            .map(|_| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                generate_announcement(
+                    viewing_public_key,
+                    spending_public_key,
+                    ephemeral_private_key,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let digest = announcements_digest(&announcements);
+        assert_eq!(digest, announcements_digest(&announcements.clone()));
+
+        let mut reordered = announcements.clone();
+        reordered.swap(0, 2);
+        assert_ne!(digest, announcements_digest(&reordered));
+        assert_ne!(digest, announcements_digest(&announcements[..2]));
+    }
+
+    #[test]
+    fn test_uncompressed_layout() {
+        // the generator is (1, 2)
+        let generator = Curve::derive_public_key(&Fr::from(1));
+        let mut expected = [0u8; 64];
+        expected[31] = 1;
+        expected[63] = 2;
+        assert_eq!(serialize_public_key_uncompressed(&generator), expected);
+        assert_eq!(
+            deserialize_public_key_uncompressed(&expected),
+            Ok(generator)
+        );
+    }
+
+    #[test]
+    fn test_uncompressed_round_trip() {
+        let (_, public_key) = Curve::random_keypair();
+        let bytes = serialize_public_key_uncompressed(&public_key);
+        assert_eq!(deserialize_public_key_uncompressed(&bytes), Ok(public_key));
+        assert_eq!(
+            crate::serialization::deserialize_public_key_any::<Curve>(&bytes),
+            Ok(public_key)
+        );
+    }
+
+    #[test]
+    fn test_uncompressed_rejects_invalid_points() {
+        let mut off_curve = [0u8; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 3;
+        assert_eq!(
+            deserialize_public_key_uncompressed(&off_curve),
+            Err(StealthError::InvalidEncoding)
+        );
+
+        let identity = serialize_public_key_uncompressed(&G1Projective::zero());
+        assert_eq!(identity, [0u8; 64]);
+        assert_eq!(
+            deserialize_public_key_uncompressed(&identity),
+            Err(StealthError::IdentityPoint)
+        );
+
+        let mut non_canonical =
+            serialize_public_key_uncompressed(&Curve::derive_public_key(&Fr::from(1)));
+        non_canonical[..32].copy_from_slice(&Fq::MODULUS.to_bytes_be());
+        assert_eq!(
+            deserialize_public_key_uncompressed(&non_canonical),
+            Err(StealthError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn test_stealth_rln_witness() -> Result<()> {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        let stealth_private_key = recover(&announcement, viewing_key, spending_key).unwrap();
+
+        let witness = stealth_rln_witness(stealth_private_key, 3);
+        assert_eq!(witness.identity_secret, stealth_private_key);
+        assert_eq!(witness.leaf_index, 3);
+
+        let resources = Cursor::new(json!({"resources_folder": "tree_height_20"}).to_string());
+        let mut rln = RLN::new(20, resources)?;
+        rln.set_leaf(
+            witness.leaf_index,
+            Cursor::new(fr_to_bytes_le(&witness.identity_commitment)),
+        )?;
+        let mut leaf = Cursor::new(Vec::new());
+        rln.get_leaf(witness.leaf_index, &mut leaf)?;
+        assert_eq!(
+            bytes_le_to_fr(&leaf.into_inner()).0,
+            poseidon_hash(&[stealth_private_key])
+        );
+
+        // rln accepts the input and builds its witness from the tree
+        let input = witness.prove_input(hash_to_field(b"epoch"), b"signal");
+        rln.get_serialized_rln_witness(input.as_slice())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_announcements_as_rln_leaves() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announcements: Vec<Announcement<Curve>> = (0..3)
+            .map(|i| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if i == 1 {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                } else {
+                    generate_announcement(other_public_key, other_public_key, ephemeral_private_key)
+                        .unwrap()
+                }
+            })
+            .collect();
+
+        let leaves = scan_announcements_as_rln_leaves(&announcements, viewing_key, spending_key);
+        assert_eq!(leaves.len(), 1);
+        let (index, stealth_private_key, leaf) = leaves[0];
+        assert_eq!(index, 1);
+
+        let commitment = Curve::derive_public_key(&stealth_private_key).into_affine();
+        let expected = poseidon_hash(&[
+            Fr::from_le_bytes_mod_order(&commitment.x.into_bigint().to_bytes_le()),
+            Fr::from_le_bytes_mod_order(&commitment.y.into_bigint().to_bytes_le()),
+        ]);
+        assert_eq!(leaf, expected);
+    }
+
     // this can only be tested for bn254 since that is the curve supported by RLN
     #[test]
     fn apply_stealth_membership_from_one_tree_to_another() -> Result<()> {
@@ -128,12 +532,12 @@ mod tests {
             alice_known_spending_pk,
             alice_known_spending_pk,
             ephemeral_private_key,
-        );
+        )
+        .unwrap();
 
-        let parts = [alice_stealth_commitment.x, alice_stealth_commitment.y];
-        let fr_parts = parts.map(|x| Fr::from(x.0));
-        let alice_stealth_commitment_buffer =
-            Cursor::new(fr_to_bytes_le(&poseidon_hash(&fr_parts)));
+        let alice_stealth_commitment_buffer = Cursor::new(fr_to_bytes_le(&commitment_to_rln_leaf(
+            &alice_stealth_commitment,
+        )));
         rln_app_tree.set_leaf(0, alice_stealth_commitment_buffer)?;
 
         // now alice's stealth commitment has been inserted into the tree, but alice has not
@@ -158,4 +562,96 @@ mod tests {
         // derived from her commitment on the other tree
         Ok(())
     }
+
+    #[test]
+    fn test_hash_to_fr_empty_input_is_pinned() {
+        assert_eq!(
+            Curve::hash_to_fr(&[]),
+            Fr::from_str(
+                "2926238299917809153455457033853348895054094980268742068263977561222919063394"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Curve::hash_to_fr(&[]),
+            hash_to_fr_with_domain::<Curve>(EMPTY_INPUT_DOMAIN, &[])
+        );
+    }
+
+    // the single-key flow of apply_stealth_membership_from_one_tree_to_another, through the
+    // meta-address API
+    #[test]
+    fn apply_stealth_membership_with_single_key() -> Result<()> {
+        let test_tree_height = 20;
+        let resources = Cursor::new(json!({"resources_folder": "tree_height_20"}).to_string());
+        let mut rln = RLN::new(test_tree_height, resources.clone())?;
+
+        let alice_leaf = Fr::rand(&mut thread_rng());
+        let alice_key = Keypair::<Curve>::random();
+        rln.set_leaf(0, Cursor::new(fr_to_bytes_le(&alice_leaf)))?;
+        let alice_meta_address = StealthMetaAddress::single_key(&alice_key)?;
+
+        let mut rln_app_tree = RLN::new(test_tree_height, resources)?;
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            alice_meta_address.viewing_public_key,
+            alice_meta_address.spending_public_key,
+            ephemeral_private_key,
+        )?;
+        let leaf = commitment_to_rln_leaf(&announcement.stealth_commitment);
+        rln_app_tree.set_leaf(0, Cursor::new(fr_to_bytes_le(&leaf)))?;
+
+        let alice_stealth_private_key = recover_single_key(&announcement, alice_key.private_key)
+            .ok_or_else(|| Report::msg("Invalid view tag"))?;
+        assert_eq!(
+            commitment_to_rln_leaf(&Curve::derive_public_key(&alice_stealth_private_key)),
+            leaf
+        );
+        let mut buffer = Cursor::new(Vec::new());
+        rln_app_tree.get_leaf(0, &mut buffer)?;
+        assert_eq!(bytes_le_to_fr(&buffer.into_inner()).0, leaf);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rln_leaf_ignores_projective_representation() {
+        let (_, commitment) = Curve::random_keypair();
+        let normalized = G1Projective::from(commitment.into_affine());
+        // (x, y, z) and (x * l^2, y * l^3, z * l) are the same jacobian point
+        let l = Fq::from(7u64);
+        let scaled = G1Projective::new_unchecked(
+            normalized.x * l * l,
+            normalized.y * l * l * l,
+            normalized.z * l,
+        );
+        assert_eq!(scaled, normalized);
+        assert_ne!((scaled.x, scaled.y), (normalized.x, normalized.y));
+        assert_eq!(
+            commitment_to_rln_leaf(&scaled),
+            commitment_to_rln_leaf(&normalized)
+        );
+        assert_eq!(
+            commitment_to_rln_leaf(&commitment),
+            commitment_to_rln_leaf(&normalized)
+        );
+    }
+
+    #[test]
+    fn test_stealth_key_to_rln_credential_json() {
+        let (stealth_key, _) = Curve::random_keypair();
+        let credential: serde_json::Value =
+            serde_json::from_str(&stealth_key_to_rln_credential_json(stealth_key, 7)).unwrap();
+        let field = |name: &str| {
+            let digits = credential[name]
+                .as_str()
+                .unwrap()
+                .strip_prefix("0x")
+                .unwrap();
+            bytes_le_to_fr(&hex::decode(digits).unwrap()).0
+        };
+        assert_eq!(field("identity_secret_hash"), stealth_key);
+        assert_eq!(field("id_commitment"), poseidon_hash(&[stealth_key]));
+        assert_eq!(credential["leaf_index"], 7);
+        assert_eq!(credential.as_object().unwrap().len(), 3);
+    }
 }
@@ -0,0 +1,77 @@
+use ark_serialize::SerializationError;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StealthError {
+    #[error("invalid length: expected {expected} bytes, got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+    #[error("invalid encoding")]
+    InvalidEncoding,
+    #[error("point is the identity")]
+    IdentityPoint,
+    #[error("unsupported scheme id {0}")]
+    UnsupportedScheme(u8),
+    #[error("private key does not match public key")]
+    InconsistentKeypair,
+    #[error("announcement is not addressed to these keys")]
+    NotRecipient,
+    #[error("ephemeral private key is 0 or 1")]
+    DegenerateEphemeralKey,
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(u8),
+    #[error("missing text record {0:?}")]
+    MissingTextRecord(&'static str),
+    #[error("view tag width mismatch: announced {announced} bytes, scanning with {configured}")]
+    ViewTagWidthMismatch { announced: usize, configured: usize },
+    #[error("shared point is the identity")]
+    DegenerateSharedPoint,
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+    #[error("i/o error: {0}")]
+    Io(std::io::ErrorKind),
+}
+
+impl From<std::io::Error> for StealthError {
+    fn from(err: std::io::Error) -> Self {
+        StealthError::Io(err.kind())
+    }
+}
+
+impl From<SerializationError> for StealthError {
+    fn from(_: SerializationError) -> Self {
+        StealthError::InvalidEncoding
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_length(bytes: &[u8]) -> anyhow::Result<usize> {
+        if bytes.len() != 32 {
+            Err(StealthError::InvalidLength {
+                expected: 32,
+                actual: bytes.len(),
+            })?;
+        }
+        Ok(bytes.len())
+    }
+
+    #[test]
+    fn test_error_propagates_into_anyhow() {
+        assert_eq!(parse_length(&[0; 32]).unwrap(), 32);
+        let err = parse_length(&[0; 31]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid length: expected 32 bytes, got 31");
+        assert_eq!(
+            err.downcast_ref::<StealthError>(),
+            Some(&StealthError::InvalidLength {
+                expected: 32,
+                actual: 31
+            })
+        );
+        assert_eq!(
+            StealthError::UnsupportedScheme(9).to_string(),
+            "unsupported scheme id 9"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Decoding announcements straight from raw EVM logs emitted by an announcer contract with the
+//! event
+//!
+//! ```solidity
+//! event Announcement(uint256 indexed schemeId, bytes stealthAddress, bytes ephemeralPubKey, bytes metadata);
+//! ```
+//!
+//! The stealth address is a compressed curve point rather than an EVM address, so unlike the
+//! secp256k1 event in ERC-5564 it is carried in the data. The metadata starts with a one byte
+//! view tag width and the low bytes of the view tag in little-endian order, followed by any
+//! free-form metadata.
+
+use crate::announcement::Announcement;
+use crate::error::StealthError;
+use crate::scheme_registry;
+use crate::serialization::{deserialize_public_key, deserialize_public_key_any, serialize_point};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use crate::view_tag::{is_valid_view_tag_width, VIEW_TAG_BYTE_WIDTH};
+use tiny_keccak::{Hasher, Keccak};
+
+pub const ANNOUNCEMENT_EVENT_SIGNATURE: &str = "Announcement(uint256,bytes,bytes,bytes)";
+
+const WORD: usize = 32;
+
+/// `topics[0]` of an announcement log, the keccak-256 of [`ANNOUNCEMENT_EVENT_SIGNATURE`].
+pub fn announcement_event_topic() -> [u8; 32] {
+    let mut topic = [0; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(ANNOUNCEMENT_EVENT_SIGNATURE.as_bytes());
+    hasher.finalize(&mut topic);
+    topic
+}
+
+/// `value` as a big-endian ABI word.
+fn word(value: usize) -> [u8; WORD] {
+    let mut word = [0; WORD];
+    word[WORD - std::mem::size_of::<u64>()..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+/// `abi.encode(bytes, bytes, ...)`: the offset of each argument, then each argument as its
+/// length followed by its bytes zero padded to a whole word.
+fn abi_encode(args: &[&[u8]]) -> Vec<u8> {
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    for arg in args {
+        head.extend(word(args.len() * WORD + tail.len()));
+        tail.extend(word(arg.len()));
+        tail.extend(*arg);
+        tail.resize(tail.len().next_multiple_of(WORD), 0);
+    }
+    head.extend(tail);
+    head
+}
+
+/// Reads a big-endian ABI word as a `usize`, rejecting values that do not fit.
+fn read_usize(data: &[u8], at: usize) -> Result<usize, StealthError> {
+    let word = at
+        .checked_add(WORD)
+        .and_then(|end| data.get(at..end))
+        .ok_or(StealthError::InvalidEncoding)?;
+    let (high, low) = word.split_at(WORD - std::mem::size_of::<u64>());
+    if high.iter().any(|byte| *byte != 0) {
+        return Err(StealthError::InvalidEncoding);
+    }
+    let value = u64::from_be_bytes(low.try_into().map_err(|_| StealthError::InvalidEncoding)?);
+    usize::try_from(value).map_err(|_| StealthError::InvalidEncoding)
+}
+
+/// The `index`th dynamic `bytes` argument of ABI encoded log data.
+fn read_bytes(data: &[u8], index: usize) -> Result<&[u8], StealthError> {
+    let offset = read_usize(data, index * WORD)?;
+    let len = read_usize(data, offset)?;
+    offset
+        .checked_add(WORD)
+        .and_then(|start| Some(start..start.checked_add(len)?))
+        .and_then(|range| data.get(range))
+        .ok_or(StealthError::InvalidEncoding)
+}
+
+impl<C: StealthAddressOnCurve> Announcement<C> {
+    /// The data of the announcement log for this announcement, as decoded by
+    /// [`Announcement::from_evm_log`].
+    pub fn to_evm_log_data(&self) -> Vec<u8> {
+        let view_tag_width = self.view_tag_width.min(VIEW_TAG_BYTE_WIDTH);
+        let mut metadata = vec![view_tag_width as u8];
+        metadata.extend(&self.view_tag.to_le_bytes()[..view_tag_width]);
+        metadata.extend(&self.metadata);
+        abi_encode(&[
+            &serialize_point(&self.stealth_commitment),
+            &serialize_point(&self.ephemeral_public_key),
+            &metadata,
+        ])
+    }
+
+    /// The size in bytes of [`Announcement::to_evm_log_data`], including the metadata, for
+    /// wallets estimating the gas of an announcement before broadcasting it.
+    pub fn calldata_size(&self) -> usize {
+        self.to_evm_log_data().len()
+    }
+
+    /// Decodes an announcement from the topics and data of an announcement log. The scheme id
+    /// topic must name the curve `C`. The ephemeral public key may be compressed or uncompressed.
+    pub fn from_evm_log(topics: &[[u8; 32]], data: &[u8]) -> Result<Self, StealthError> {
+        let [event, scheme_id] = topics else {
+            return Err(StealthError::InvalidLength {
+                expected: 2,
+                actual: topics.len(),
+            });
+        };
+        if *event != announcement_event_topic() {
+            return Err(StealthError::InvalidEncoding);
+        }
+        let scheme_id =
+            u8::try_from(read_usize(scheme_id, 0)?).map_err(|_| StealthError::InvalidEncoding)?;
+        if scheme_registry::lookup(scheme_id)? != C::SCHEME {
+            return Err(StealthError::UnsupportedScheme(scheme_id));
+        }
+
+        let (view_tag_width, metadata) = read_bytes(data, 2)?
+            .split_first()
+            .ok_or(StealthError::InvalidEncoding)?;
+        let view_tag_width = usize::from(*view_tag_width);
+        if !is_valid_view_tag_width(view_tag_width) {
+            return Err(StealthError::InvalidEncoding);
+        }
+        if metadata.len() < view_tag_width {
+            return Err(StealthError::InvalidLength {
+                expected: 1 + view_tag_width,
+                actual: 1 + metadata.len(),
+            });
+        }
+        let (view_tag, metadata) = metadata.split_at(view_tag_width);
+        let mut padded = [0; VIEW_TAG_BYTE_WIDTH];
+        padded[..view_tag_width].copy_from_slice(view_tag);
+        Ok(Announcement::new(
+            deserialize_public_key(read_bytes(data, 0)?)?,
+            deserialize_public_key_any::<C>(read_bytes(data, 1)?)?,
+            u64::from_le_bytes(padded),
+        )
+        .with_view_tag_width(view_tag_width)
+        .with_metadata(metadata.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+
+    type Curve = crate::TestCurve;
+
+    fn announcement_log(announcement: &Announcement<Curve>) -> ([[u8; 32]; 2], Vec<u8>) {
+        (
+            [announcement_event_topic(), word(Curve::SCHEME.id().into())],
+            announcement.to_evm_log_data(),
+        )
+    }
+
+    #[test]
+    fn test_from_evm_log() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap()
+        .with_metadata(vec![0xde, 0xad, 0xbe, 0xef]);
+
+        let (topics, data) = announcement_log(&announcement);
+        assert_eq!(
+            Announcement::from_evm_log(&topics, &data),
+            Ok(announcement.clone())
+        );
+
+        let truncated = announcement.with_view_tag_width(1);
+        let (topics, data) = announcement_log(&truncated);
+        let decoded = Announcement::<Curve>::from_evm_log(&topics, &data).unwrap();
+        assert_eq!(decoded.view_tag_width, 1);
+        assert_eq!(decoded, truncated);
+    }
+
+    #[test]
+    fn test_from_evm_log_rejects_malformed_logs() {
+        let (_, public_key) = Curve::random_keypair();
+        let announcement = Announcement::<Curve>::new(public_key, public_key, 7);
+        let (topics, data) = announcement_log(&announcement);
+
+        let mut other_event = topics;
+        other_event[0][0] ^= 1;
+        let mut other_scheme = topics;
+        other_scheme[1] = word(1);
+        for topics in [&topics[..1], &other_event, &other_scheme] {
+            assert!(Announcement::<Curve>::from_evm_log(topics, &data).is_err());
+        }
+
+        let mut bad_offset = data.clone();
+        bad_offset[..32].copy_from_slice(&[0xff; 32]);
+        for data in [&data[..data.len() - 40], &data[..64], &[][..], &bad_offset] {
+            assert!(Announcement::<Curve>::from_evm_log(&topics, data).is_err());
+        }
+
+        // the width byte opens the last word, the metadata being only the width and tag
+        for width in [0, VIEW_TAG_BYTE_WIDTH as u8 + 1] {
+            let mut bad_width = data.clone();
+            let at = bad_width.len() - WORD;
+            bad_width[at] = width;
+            assert_eq!(
+                Announcement::<Curve>::from_evm_log(&topics, &bad_width),
+                Err(StealthError::InvalidEncoding)
+            );
+        }
+    }
+
+    #[test]
+    fn test_calldata_size() {
+        let (_, public_key) = Curve::random_keypair();
+        let announcement = Announcement::<Curve>::new(public_key, public_key, 7);
+        let point = serialize_point(&public_key);
+        for metadata in [vec![], vec![0xab; 23], vec![0xcd; 24], vec![0xef; 100]] {
+            let mut encoded_metadata = vec![VIEW_TAG_BYTE_WIDTH as u8];
+            encoded_metadata.extend(7u64.to_le_bytes());
+            encoded_metadata.extend(&metadata);
+            let announcement = announcement.clone().with_metadata(metadata);
+            assert_eq!(
+                announcement.calldata_size(),
+                abi_encode(&[&point, &point, &encoded_metadata]).len()
+            );
+        }
+        // heads, lengths, one word per 32 byte point and the 9 byte width and tag padded to a word
+        assert_eq!(
+            announcement.calldata_size(),
+            6 * WORD + 2 * point.len().next_multiple_of(WORD) + WORD
+        );
+    }
+}
@@ -2,10 +2,14 @@ use crate::ffi::CErrorCode::{
     NoError, SerializationErrorInvalidData, SerializationErrorIoError,
     SerializationErrorNotEnoughSpace, SerializationErrorUnexpectedFlags,
 };
+use crate::announcement::Announcement;
+use crate::scan::scan_announcements;
 use crate::stealth_commitments::{StealthAddressOnCurve};
+use ark_ff::PrimeField;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use num_traits::Zero;
 use std::ops::Add;
+use std::sync::RwLock;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "bls12_381")] {
@@ -43,6 +47,9 @@ pub enum CErrorCode {
     SerializationErrorUnexpectedFlags = 3,
     SerializationErrorIoError = 4,
     InvalidKeys = 5,
+    NonCanonicalScalar = 6,
+    InvalidBuffer = 7,
+    BufferTooSmall = 8,
 }
 
 impl From<SerializationError> for CErrorCode {
@@ -109,6 +116,14 @@ impl From<&CFr> for Fr {
     }
 }
 
+/// Decodes a scalar, rejecting encodings of values >= the field modulus so that distinct
+/// inputs can never map to the same key.
+fn canonical_fr(value: &CFr) -> Result<Fr, CErrorCode> {
+    let repr = <Fr as PrimeField>::BigInt::deserialize_compressed(value.0.as_slice())
+        .map_err(|_| CErrorCode::InvalidKeys)?;
+    Fr::from_bigint(repr).ok_or(CErrorCode::NonCanonicalScalar)
+}
+
 #[repr(C)]
 #[derive(Debug, PartialOrd, PartialEq)]
 pub struct CG1Projective([u8; PROJECTIVE_SIZE]);
@@ -207,6 +222,28 @@ impl TryInto<(G1Projective, u64)> for CStealthCommitment {
     }
 }
 
+/// A host supplied replacement for the keccak-256 inside `hash_to_fr`. It must write the 32 byte
+/// digest of the `len` bytes at `input` to `out`.
+pub type HashBackend = extern "C" fn(input: *const u8, len: usize, out: *mut u8);
+
+static HASH_BACKEND: RwLock<Option<HashBackend>> = RwLock::new(None);
+
+/// Routes the keccak-256 inside `hash_to_fr` through `callback`, e.g. a hardware-accelerated
+/// keccak. A null callback restores the built-in keccak. The backend is process-wide, and must
+/// compute keccak-256 exactly, or commitments will not match other implementations.
+#[no_mangle]
+pub extern "C" fn set_hash_backend(callback: Option<HashBackend>) {
+    *HASH_BACKEND.write().unwrap_or_else(|err| err.into_inner()) = callback;
+}
+
+/// The digest of `input` from the registered [`HashBackend`], if one is set.
+pub(crate) fn hash_backend_digest(input: &[u8]) -> Option<[u8; 32]> {
+    let callback = (*HASH_BACKEND.read().unwrap_or_else(|err| err.into_inner()))?;
+    let mut digest = [0; 32];
+    callback(input.as_ptr(), input.len(), digest.as_mut_ptr());
+    Some(digest)
+}
+
 #[no_mangle]
 pub extern "C" fn ffi_generate_random_fr() -> *mut CReturn<CFr> {
     let res = match CFr::try_from(Curve::generate_random_fr()) {
@@ -243,12 +280,12 @@ pub extern "C" fn ffi_derive_public_key(private_key: *mut CFr) -> *mut CReturn<C
         }
         &*private_key
     };
-    let private_key: Fr = match private_key.try_into() {
+    let private_key = match canonical_fr(private_key) {
         Ok(v) => v,
-        Err(_) => {
+        Err(err_code) => {
             return Box::into_raw(Box::new(CReturn {
                 value: CG1Projective::zero(),
-                err_code: CErrorCode::InvalidKeys,
+                err_code,
             }))
         }
     };
@@ -369,20 +406,29 @@ pub extern "C" fn ffi_generate_stealth_commitment(
             }))
         }
     };
-    let ephemeral_private_key: Fr = match ephemeral_private_key.try_into() {
+    let ephemeral_private_key = match canonical_fr(ephemeral_private_key) {
         Ok(v) => v,
-        Err(_) => {
+        Err(err_code) => {
             return Box::into_raw(Box::new(CReturn {
                 value: CStealthCommitment::zero(),
-                err_code: CErrorCode::InvalidKeys,
+                err_code,
             }))
         }
     };
-    let res = match CStealthCommitment::try_from(Curve::generate_stealth_commitment(
+    let stealth_commitment = match Curve::generate_stealth_commitment(
         viewing_public_key,
         spending_public_key,
         ephemeral_private_key,
-    )) {
+    ) {
+        Ok(v) => v,
+        Err(_) => {
+            return Box::into_raw(Box::new(CReturn {
+                value: CStealthCommitment::zero(),
+                err_code: CErrorCode::InvalidKeys,
+            }))
+        }
+    };
+    let res = match CStealthCommitment::try_from(stealth_commitment) {
         Ok(v) => CReturn {
             value: v,
             err_code: NoError,
@@ -460,21 +506,21 @@ pub extern "C" fn ffi_generate_stealth_private_key(
             }))
         }
     };
-    let spending_key: Fr = match spending_key.try_into() {
+    let spending_key = match canonical_fr(spending_key) {
         Ok(v) => v,
-        Err(_) => {
+        Err(err_code) => {
             return Box::into_raw(Box::new(CReturn {
                 value: CFr::zero(),
-                err_code: CErrorCode::InvalidKeys,
+                err_code,
             }))
         }
     };
-    let viewing_key: Fr = match viewing_key.try_into() {
+    let viewing_key = match canonical_fr(viewing_key) {
         Ok(v) => v,
-        Err(_) => {
+        Err(err_code) => {
             return Box::into_raw(Box::new(CReturn {
                 value: CFr::zero(),
-                err_code: CErrorCode::InvalidKeys,
+                err_code,
             }))
         }
     };
@@ -509,11 +555,117 @@ pub extern "C" fn drop_ffi_generate_stealth_private_key(ptr: *mut CReturn<CFr>)
     }
 }
 
+/// Decodes `count` announcements, one every `stride` bytes from `announcements`, each in the
+/// `Announcement::to_bytes` encoding followed by any padding up to the stride. The buffer must
+/// hold `count * stride` bytes, including the padding of the last record.
+fn read_announcements(
+    announcements: *const u8,
+    count: usize,
+    stride: usize,
+) -> Result<Vec<Announcement<Curve>>, CErrorCode> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let record_size = Announcement::<Curve>::encoded_size();
+    let len = count.checked_mul(stride).ok_or(CErrorCode::InvalidBuffer)?;
+    if announcements.is_null() || stride < record_size || len > isize::MAX as usize {
+        return Err(CErrorCode::InvalidBuffer);
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(announcements, len) };
+    bytes
+        .chunks_exact(stride)
+        .map(|record| {
+            Announcement::from_bytes(&record[..record_size])
+                .map_err(|_| SerializationErrorInvalidData)
+        })
+        .collect()
+}
+
+/// Scans `count` serialized announcements laid out every `stride` bytes, writing the index and
+/// stealth private key of each match to `out_indices` and `out_stealth_keys`, both with room for
+/// `capacity` entries. The value is the number of matches. If there are more than `capacity`,
+/// nothing is written and the error is `BufferTooSmall`, with the value the capacity needed.
+#[no_mangle]
+pub extern "C" fn ffi_scan_batch(
+    announcements: *const u8,
+    count: usize,
+    stride: usize,
+    viewing_key: *mut CFr,
+    spending_key: *mut CFr,
+    out_indices: *mut usize,
+    out_stealth_keys: *mut CFr,
+    capacity: usize,
+) -> *mut CReturn<usize> {
+    let res = match scan_batch(
+        announcements,
+        count,
+        stride,
+        viewing_key,
+        spending_key,
+        out_indices,
+        out_stealth_keys,
+        capacity,
+    ) {
+        Ok(value) => CReturn {
+            value,
+            err_code: NoError,
+        },
+        Err((value, err_code)) => CReturn { value, err_code },
+    };
+    Box::into_raw(Box::new(res))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn scan_batch(
+    announcements: *const u8,
+    count: usize,
+    stride: usize,
+    viewing_key: *mut CFr,
+    spending_key: *mut CFr,
+    out_indices: *mut usize,
+    out_stealth_keys: *mut CFr,
+    capacity: usize,
+) -> Result<usize, (usize, CErrorCode)> {
+    if viewing_key.is_null() || spending_key.is_null() {
+        return Err((0, CErrorCode::InvalidKeys));
+    }
+    let viewing_key = canonical_fr(unsafe { &*viewing_key }).map_err(|err| (0, err))?;
+    let spending_key = canonical_fr(unsafe { &*spending_key }).map_err(|err| (0, err))?;
+    let announcements = read_announcements(announcements, count, stride).map_err(|err| (0, err))?;
+
+    let matches = scan_announcements(&announcements, viewing_key, spending_key);
+    if matches.len() > capacity {
+        return Err((matches.len(), CErrorCode::BufferTooSmall));
+    }
+    if !matches.is_empty() && (out_indices.is_null() || out_stealth_keys.is_null()) {
+        return Err((0, CErrorCode::InvalidBuffer));
+    }
+    for (i, result) in matches.iter().enumerate() {
+        let stealth_key = CFr::try_from(result.stealth_private_key).map_err(|err| (0, err.into()))?;
+        unsafe {
+            out_indices.add(i).write(result.index);
+            out_stealth_keys.add(i).write(stealth_key);
+        }
+    }
+    Ok(matches.len())
+}
+
+#[no_mangle]
+pub extern "C" fn drop_ffi_scan_batch(ptr: *mut CReturn<usize>) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        let _ = Box::from_raw(ptr);
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use ark_ec::CurveGroup;
+    use ark_ff::BigInteger;
 
     #[test]
     fn test_ffi_generate_random_fr() {
@@ -599,4 +751,137 @@ mod tests {
         drop_ffi_generate_stealth_commitment(stealth_commitment_payload_raw);
         drop_ffi_derive_public_key(derived_commitment_raw);
     }
+
+    #[test]
+    fn test_ffi_rejects_non_canonical_scalar() {
+        let mut modulus = CFr([0u8; 32]);
+        modulus.0.copy_from_slice(&Fr::MODULUS.to_bytes_le());
+
+        let public_key_raw = ffi_derive_public_key(&mut modulus);
+        let public_key = unsafe { &*public_key_raw };
+        assert_eq!(public_key.err_code, CErrorCode::NonCanonicalScalar);
+        drop_ffi_derive_public_key(public_key_raw);
+
+        let keypair_raw = ffi_random_keypair();
+        let keypair = unsafe { &mut *keypair_raw };
+        let stealth_commitment_raw = ffi_generate_stealth_commitment(
+            &mut keypair.value.public_key,
+            &mut keypair.value.public_key,
+            &mut modulus,
+        );
+        let stealth_commitment = unsafe { &*stealth_commitment_raw };
+        assert_eq!(
+            stealth_commitment.err_code,
+            CErrorCode::NonCanonicalScalar
+        );
+        drop_ffi_generate_stealth_commitment(stealth_commitment_raw);
+        drop_ffi_random_keypair(keypair_raw);
+    }
+
+    static BACKEND_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    extern "C" fn counting_keccak(input: *const u8, len: usize, out: *mut u8) {
+        use tiny_keccak::{Hasher, Keccak};
+        BACKEND_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let input = unsafe { std::slice::from_raw_parts(input, len) };
+        let out = unsafe { std::slice::from_raw_parts_mut(out, 32) };
+        let mut hasher = Keccak::v256();
+        hasher.update(input);
+        hasher.finalize(out);
+    }
+
+    #[test]
+    fn test_set_hash_backend() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let expected = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        // the backend computes the same keccak, so concurrently running tests are unaffected
+        set_hash_backend(Some(counting_keccak));
+        let calls = BACKEND_CALLS.load(std::sync::atomic::Ordering::SeqCst);
+        let commitment = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert!(BACKEND_CALLS.load(std::sync::atomic::Ordering::SeqCst) > calls);
+        assert_eq!(commitment, expected);
+        let stealth_private_key = Curve::generate_stealth_private_key(
+            ephemeral_public_key,
+            viewing_key,
+            spending_key,
+            commitment.1,
+        )
+        .unwrap();
+        assert_eq!(Curve::derive_public_key(&stealth_private_key), commitment.0);
+
+        set_hash_backend(None);
+        assert_eq!(hash_backend_digest(b"input"), None);
+    }
+
+    #[test]
+    fn test_ffi_scan_batch() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announcements: Vec<_> = (0..12)
+            .map(|i| {
+                let recipient = if i % 4 == 1 { viewing_public_key } else { other_public_key };
+                crate::announcement::generate_announcement::<Curve>(
+                    recipient,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+            })
+            .collect();
+        let expected = scan_announcements(&announcements, viewing_key, spending_key);
+        assert_eq!(expected.len(), 3);
+
+        // padded records, to exercise the stride
+        let stride = Announcement::<Curve>::encoded_size() + 3;
+        let mut bytes = Vec::new();
+        for announcement in &announcements {
+            bytes.extend(announcement.to_bytes());
+            bytes.extend([0xaa; 3]);
+        }
+        let mut viewing_key = CFr::try_from(viewing_key).unwrap();
+        let mut spending_key = CFr::try_from(spending_key).unwrap();
+        let mut indices = vec![0usize; 4];
+        let mut stealth_keys: Vec<CFr> = (0..4).map(|_| CFr::zero()).collect();
+        let mut scan = |count: usize, stride: usize, capacity: usize| {
+            let raw = ffi_scan_batch(
+                bytes.as_ptr(),
+                count,
+                stride,
+                &mut viewing_key,
+                &mut spending_key,
+                indices.as_mut_ptr(),
+                stealth_keys.as_mut_ptr(),
+                capacity,
+            );
+            let res = unsafe { &*raw };
+            let res = (res.value, res.err_code == NoError);
+            drop_ffi_scan_batch(raw);
+            res
+        };
+
+        assert_eq!(scan(announcements.len(), stride, 4), (3, true));
+        assert_eq!(scan(announcements.len(), stride, 2), (3, false));
+        assert_eq!(scan(announcements.len(), stride - 4, 4), (0, false));
+        assert_eq!(scan(usize::MAX, stride, 4), (0, false));
+        assert_eq!(scan(0, stride, 0), (0, true));
+        assert_eq!(scan(announcements.len(), stride, 4), (3, true));
+        for (i, result) in expected.iter().enumerate() {
+            assert_eq!(indices[i], result.index);
+            assert_eq!(Fr::from(&stealth_keys[i]), result.stealth_private_key);
+        }
+    }
 }
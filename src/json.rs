@@ -0,0 +1,170 @@
+//! The JSON wire format for announcements exchanged between indexers and wallets:
+//!
+//! ```json
+//! {"scheme_id":2,"stealth_address":"0x…","ephemeral_public_key":"0x…","view_tag":"0x…","metadata":"0x…"}
+//! ```
+//!
+//! Points are compressed, and all byte strings are `0x` prefixed lowercase hex. The view tag is
+//! the big-endian hex of its low [`Announcement::view_tag_width`] bytes, so the number of digits
+//! carries the width, 16 digits for a full tag. As a string it survives JSON parsers that read
+//! numbers as doubles.
+
+use crate::announcement::Announcement;
+use crate::error::StealthError;
+use crate::scheme_registry;
+use crate::serialization::{deserialize_public_key, serialize_point};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use crate::view_tag::{is_valid_view_tag_width, VIEW_TAG_BYTE_WIDTH};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+struct AnnouncementJson {
+    scheme_id: u8,
+    stealth_address: String,
+    ephemeral_public_key: String,
+    view_tag: String,
+    metadata: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(value: &str) -> Result<Vec<u8>, StealthError> {
+    let digits = value
+        .strip_prefix("0x")
+        .ok_or(StealthError::InvalidEncoding)?;
+    hex::decode(digits).map_err(|_| StealthError::InvalidEncoding)
+}
+
+impl<C: StealthAddressOnCurve> Serialize for Announcement<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AnnouncementJson {
+            scheme_id: C::SCHEME.id(),
+            stealth_address: to_hex(&serialize_point(&self.stealth_commitment)),
+            ephemeral_public_key: to_hex(&serialize_point(&self.ephemeral_public_key)),
+            view_tag: to_hex(
+                &self.view_tag.to_be_bytes()
+                    [VIEW_TAG_BYTE_WIDTH - self.view_tag_width.min(VIEW_TAG_BYTE_WIDTH)..],
+            ),
+            metadata: to_hex(&self.metadata),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<C: StealthAddressOnCurve> TryFrom<AnnouncementJson> for Announcement<C> {
+    type Error = StealthError;
+
+    fn try_from(json: AnnouncementJson) -> Result<Self, Self::Error> {
+        if scheme_registry::lookup(json.scheme_id)? != C::SCHEME {
+            return Err(StealthError::UnsupportedScheme(json.scheme_id));
+        }
+        let view_tag = from_hex(&json.view_tag)?;
+        if !is_valid_view_tag_width(view_tag.len()) {
+            return Err(StealthError::InvalidLength {
+                expected: VIEW_TAG_BYTE_WIDTH,
+                actual: view_tag.len(),
+            });
+        }
+        let mut padded = [0; VIEW_TAG_BYTE_WIDTH];
+        padded[VIEW_TAG_BYTE_WIDTH - view_tag.len()..].copy_from_slice(&view_tag);
+        Ok(Announcement::new(
+            deserialize_public_key(&from_hex(&json.stealth_address)?)?,
+            deserialize_public_key(&from_hex(&json.ephemeral_public_key)?)?,
+            u64::from_be_bytes(padded),
+        )
+        .with_view_tag_width(view_tag.len())
+        .with_metadata(from_hex(&json.metadata)?))
+    }
+}
+
+impl<'de, C: StealthAddressOnCurve> Deserialize<'de> for Announcement<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AnnouncementJson::deserialize(deserializer)?
+            .try_into()
+            .map_err(|err| D::Error::custom(format!("invalid announcement: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Curve = crate::TestCurve;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    fn known_announcement() -> Announcement<Curve> {
+        Announcement::new(
+            Curve::derive_public_key(&Fr::from(2u64)),
+            Curve::derive_public_key(&Fr::from(3u64)),
+            0x0123_4567_89ab_cdef,
+        )
+        .with_metadata(vec![0xab, 0xcd])
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "bls12_381")] {
+            const KNOWN_JSON: &str = concat!(
+                r#"{"scheme_id":3,"#,
+                r#""stealth_address":"0xa572cbea904d67468808c8eb50a9450c9721db309128012543902d0ac358a62ae28f75bb8f1c7c42c39a8c5529bf0f4e","#,
+                r#""ephemeral_public_key":"0x89ece308f9d1f0131765212deca99697b112d61f9be9a5f1f3780a51335b3ff981747a0b2ca2179b96d2c0c9024e5224","#,
+                r#""view_tag":"0x0123456789abcdef","metadata":"0xabcd"}"#
+            );
+        } else if #[cfg(feature = "bls12_377")] {
+            const KNOWN_JSON: &str = concat!(
+                r#"{"scheme_id":4,"#,
+                r#""stealth_address":"0x9063416a6ded7a8590dc816765610688551930a2c9970ee97e4b2addf3f7617eed52544b5adb6e05919e93413145ed00","#,
+                r#""ephemeral_public_key":"0x2eecf6dc04c6ab15f7ce968dbd17d8636e215d6af6112e71a90ca2903854461a911f3a431b2936db07f57111782b2581","#,
+                r#""view_tag":"0x0123456789abcdef","metadata":"0xabcd"}"#
+            );
+        } else if #[cfg(feature = "bn254")] {
+            const KNOWN_JSON: &str = concat!(
+                r#"{"scheme_id":2,"#,
+                r#""stealth_address":"0xd3cf876dc108c2d3a81c8716a91678d9851518685b04859b021a132ee7440603","#,
+                r#""ephemeral_public_key":"0xf0ab15199655d3f279e6b81547d8159315bdb6b1bc3202f43fea6bc59abf6987","#,
+                r#""view_tag":"0x0123456789abcdef","metadata":"0xabcd"}"#
+            );
+        }
+    }
+
+    #[test]
+    fn test_announcement_json_is_pinned() {
+        let announcement = known_announcement();
+        let json = serde_json::to_string(&announcement).unwrap();
+        assert_eq!(json, KNOWN_JSON);
+        assert_eq!(
+            serde_json::from_str::<Announcement<Curve>>(&json).unwrap(),
+            announcement
+        );
+    }
+
+    #[test]
+    fn test_announcement_json_carries_view_tag_width() {
+        let announcement = known_announcement().with_view_tag_width(2);
+        let json = serde_json::to_string(&announcement).unwrap();
+        assert!(json.contains(r#""view_tag":"0xcdef""#));
+        let decoded = serde_json::from_str::<Announcement<Curve>>(&json).unwrap();
+        assert_eq!(decoded.view_tag_width, 2);
+        assert_eq!(decoded, announcement);
+
+        for view_tag in ["0x", "0x000123456789abcdef"] {
+            let json = KNOWN_JSON.replace("0x0123456789abcdef", view_tag);
+            assert!(serde_json::from_str::<Announcement<Curve>>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_announcement_json_rejects_other_scheme() {
+        let json = serde_json::to_string(&known_announcement())
+            .unwrap()
+            .replacen(
+                &format!(r#""scheme_id":{}"#, Curve::SCHEME.id()),
+                r#""scheme_id":1"#,
+                1,
+            );
+        assert!(serde_json::from_str::<Announcement<Curve>>(&json).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use crate::announcement::{generate_announcement, Announcement};
+use crate::error::StealthError;
+use crate::meta_address::StealthMetaAddress;
+use crate::serialization::{
+    deserialize_fr, deserialize_public_key, deserialize_public_key_unchecked, scalar_size,
+    validate_public_key,
+};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use ark_std::rand::Rng;
+use num_traits::Zero;
+
+/// A public key that has been checked to be a valid, non-identity point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey<C: StealthAddressOnCurve>(C::Projective);
+
+impl<C: StealthAddressOnCurve> PublicKey<C> {
+    pub fn from_projective(point: C::Projective) -> Result<Self, StealthError> {
+        validate_public_key(&point)?;
+        Ok(PublicKey(point))
+    }
+
+    /// Wraps a point without validating it. The caller must guarantee the point is on the
+    /// curve, in the prime order subgroup and not the identity, e.g. because it was checked
+    /// before being stored.
+    pub fn from_projective_unchecked(point: C::Projective) -> Self {
+        PublicKey(point)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StealthError> {
+        Ok(PublicKey(deserialize_public_key(bytes)?))
+    }
+
+    /// Decodes with [`deserialize_public_key_unchecked`], under the same caller guarantee as
+    /// [`PublicKey::from_projective_unchecked`].
+    pub fn from_bytes_unchecked(bytes: &[u8]) -> Result<Self, StealthError> {
+        Ok(PublicKey(deserialize_public_key_unchecked(bytes)?))
+    }
+
+    pub fn as_projective(&self) -> &C::Projective {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keypair<C: StealthAddressOnCurve> {
+    pub private_key: C::Fr,
+    pub public_key: C::Projective,
+}
+
+impl<C: StealthAddressOnCurve> Keypair<C> {
+    pub fn new(private_key: C::Fr, public_key: C::Projective) -> Self {
+        Keypair {
+            private_key,
+            public_key,
+        }
+    }
+
+    pub fn from_private_key(private_key: C::Fr) -> Self {
+        Keypair::new(private_key, C::derive_public_key(&private_key))
+    }
+
+    /// Imports a private key as wallets export it: big-endian hex of exactly one scalar, with an
+    /// optional `0x` prefix. Keys at or above the modulus are rejected rather than reduced, so
+    /// `modulus - 1` is the largest key accepted. The zero key, whose public key is the
+    /// identity, is rejected with [`StealthError::IdentityPoint`].
+    pub fn from_hex_private_key(s: &str) -> Result<Self, StealthError> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        let expected = 2 * scalar_size::<C::Fr>();
+        if digits.len() != expected {
+            return Err(StealthError::InvalidLength {
+                expected,
+                actual: digits.len(),
+            });
+        }
+        let mut bytes = hex::decode(digits).map_err(|_| StealthError::InvalidEncoding)?;
+        bytes.reverse();
+        let private_key: C::Fr = deserialize_fr(&bytes)?;
+        if private_key.is_zero() {
+            return Err(StealthError::IdentityPoint);
+        }
+        Ok(Keypair::from_private_key(private_key))
+    }
+
+    #[cfg(feature = "os_rng")]
+    pub fn random() -> Self {
+        let (private_key, public_key) = C::random_keypair();
+        Keypair::new(private_key, public_key)
+    }
+
+    pub fn random_with<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let (private_key, public_key) = C::random_keypair_with(rng);
+        Keypair::new(private_key, public_key)
+    }
+
+    /// Checks that the public key is the one derived from the private key, e.g. for imported keys.
+    pub fn validate(&self) -> Result<(), StealthError> {
+        if C::derive_public_key(&self.private_key) == self.public_key {
+            Ok(())
+        } else {
+            Err(StealthError::InconsistentKeypair)
+        }
+    }
+}
+
+/// A deterministic keypair with private key `n`, so tests can use stable, labeled keys such as
+/// `keypair_from_u64(1)` instead of random ones. Small private keys are trivially guessable:
+/// never use these outside tests.
+///
+/// # Panics
+///
+/// If `n` is 0, which would give the identity as public key.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn keypair_from_u64<C: StealthAddressOnCurve>(n: u64) -> Keypair<C> {
+    assert!(n != 0, "a zero private key has the identity as public key");
+    Keypair::from_private_key(C::Fr::from(n))
+}
+
+/// A sender's single-use ephemeral keypair, kept apart from identity keys so that a spending or
+/// viewing [`Keypair`] cannot be announced with by mistake:
+///
+/// ```compile_fail
+/// use erc_5564_rs::keypair::Keypair;
+/// use erc_5564_rs::meta_address::StealthMetaAddress;
+/// use erc_5564_rs::stealth_commitments::StealthAddressOnCurve;
+///
+/// fn announce<C: StealthAddressOnCurve>(spending: Keypair<C>, meta: &StealthMetaAddress<C>) {
+///     // only an EphemeralKeypair can announce
+///     let _ = spending.announce(meta);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralKeypair<C: StealthAddressOnCurve>(Keypair<C>);
+
+impl<C: StealthAddressOnCurve> EphemeralKeypair<C> {
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        EphemeralKeypair(Keypair::random_with(rng))
+    }
+
+    /// Wraps a stored ephemeral private key, e.g. one kept to later prove a payment with
+    /// [`crate::announcement::audit_announcement`].
+    pub fn from_private_key(private_key: C::Fr) -> Self {
+        EphemeralKeypair(Keypair::from_private_key(private_key))
+    }
+
+    pub fn private_key(&self) -> C::Fr {
+        self.0.private_key
+    }
+
+    pub fn public_key(&self) -> C::Projective {
+        self.0.public_key
+    }
+
+    /// Announces a payment to `meta` with this key, as [`generate_announcement`].
+    pub fn announce(&self, meta: &StealthMetaAddress<C>) -> Result<Announcement<C>, StealthError> {
+        generate_announcement(
+            meta.viewing_public_key,
+            meta.spending_public_key,
+            self.0.private_key,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::SeedableRng;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_keypair_from_u64() {
+        let one = keypair_from_u64::<Curve>(1);
+        let two = keypair_from_u64::<Curve>(2);
+        assert_ne!(one, two);
+        assert_eq!(one, keypair_from_u64::<Curve>(1));
+        assert_eq!(one.validate(), Ok(()));
+        assert_eq!(two.validate(), Ok(()));
+        assert_eq!(two.public_key, one.public_key + one.public_key);
+    }
+
+    #[test]
+    fn test_validate() {
+        let keypair = Keypair::<Curve>::random();
+        assert_eq!(keypair.validate(), Ok(()));
+        assert_eq!(
+            Keypair::<Curve>::from_private_key(keypair.private_key).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_random_with_seeded_rng() {
+        let keypair = Keypair::<Curve>::random_with(&mut StdRng::seed_from_u64(1));
+        assert_eq!(keypair.validate(), Ok(()));
+        assert_eq!(
+            keypair,
+            Keypair::<Curve>::random_with(&mut StdRng::seed_from_u64(1))
+        );
+        assert_ne!(
+            keypair,
+            Keypair::<Curve>::random_with(&mut StdRng::seed_from_u64(2))
+        );
+    }
+
+    #[test]
+    fn test_validate_tampered_public_key() {
+        let mut keypair = Keypair::<Curve>::random();
+        let (_, other_public_key) = Curve::random_keypair();
+        keypair.public_key += other_public_key;
+        assert_eq!(keypair.validate(), Err(StealthError::InconsistentKeypair));
+    }
+
+    #[test]
+    fn test_unchecked_public_key_matches_checked() {
+        let (_, public_key) = Curve::random_keypair();
+        let bytes = crate::serialization::serialize_public_key(&public_key).unwrap();
+
+        assert_eq!(
+            PublicKey::<Curve>::from_projective_unchecked(public_key),
+            PublicKey::from_projective(public_key).unwrap()
+        );
+        assert_eq!(
+            PublicKey::<Curve>::from_bytes_unchecked(&bytes),
+            PublicKey::from_bytes(&bytes)
+        );
+        assert_eq!(
+            deserialize_public_key_unchecked::<<Curve as StealthAddressOnCurve>::Projective>(
+                &bytes
+            ),
+            deserialize_public_key(&bytes)
+        );
+        assert_eq!(
+            PublicKey::<Curve>::from_projective(num_traits::Zero::zero()),
+            Err(StealthError::IdentityPoint)
+        );
+    }
+
+    #[test]
+    fn test_ephemeral_keypair_announcement() {
+        let spending = Keypair::<Curve>::random();
+        let viewing = Keypair::<Curve>::random();
+        let meta = StealthMetaAddress::new(spending.public_key, viewing.public_key);
+
+        let ephemeral = EphemeralKeypair::<Curve>::random(&mut StdRng::seed_from_u64(3));
+        let announcement = ephemeral.announce(&meta).unwrap();
+        assert_eq!(announcement.ephemeral_public_key, ephemeral.public_key());
+        assert_eq!(
+            announcement,
+            EphemeralKeypair::from_private_key(ephemeral.private_key())
+                .announce(&meta)
+                .unwrap()
+        );
+        assert!(
+            crate::scan::recover(&announcement, viewing.private_key, spending.private_key)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_from_hex_private_key() {
+        type Fr = <Curve as StealthAddressOnCurve>::Fr;
+        let (private_key, public_key) = Curve::random_keypair();
+        let mut be_bytes = crate::serialization::serialize_fr(&private_key);
+        be_bytes.reverse();
+        let digits = hex::encode(&be_bytes);
+
+        let keypair = Keypair::<Curve>::from_hex_private_key(&format!("0x{digits}")).unwrap();
+        assert_eq!(keypair, Keypair::new(private_key, public_key));
+        assert_eq!(Keypair::<Curve>::from_hex_private_key(&digits), Ok(keypair));
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&digits.to_uppercase()),
+            Keypair::<Curve>::from_hex_private_key(&digits)
+        );
+
+        let modulus = Fr::MODULUS.to_bytes_be();
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&format!("0x{}", hex::encode(modulus))),
+            Err(StealthError::InvalidEncoding)
+        );
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&format!("0x{}", &digits[2..])),
+            Err(StealthError::InvalidLength {
+                expected: 64,
+                actual: 62
+            })
+        );
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&format!("0x{}zz", &digits[2..])),
+            Err(StealthError::InvalidEncoding)
+        );
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&"0".repeat(digits.len())),
+            Err(StealthError::IdentityPoint)
+        );
+    }
+
+    #[test]
+    fn test_largest_scalar_is_a_valid_key() {
+        type Fr = <Curve as StealthAddressOnCurve>::Fr;
+        let largest = -Fr::from(1u64);
+        assert_eq!(largest.into_bigint(), {
+            let mut modulus = Fr::MODULUS;
+            modulus.sub_with_borrow(&Fr::from(1u64).into_bigint());
+            modulus
+        });
+
+        let keypair = Keypair::<Curve>::from_private_key(largest);
+        assert_eq!(keypair.validate(), Ok(()));
+        assert_eq!(
+            keypair.public_key,
+            -Curve::derive_public_key(&Fr::from(1u64))
+        );
+        let bytes = crate::serialization::serialize_fr(&largest);
+        assert_eq!(
+            crate::serialization::deserialize_fr::<Fr>(&bytes),
+            Ok(largest)
+        );
+        let mut be_bytes = bytes;
+        be_bytes.reverse();
+        assert_eq!(
+            Keypair::<Curve>::from_hex_private_key(&hex::encode(be_bytes)),
+            Ok(keypair.clone())
+        );
+
+        // as spending, viewing and ephemeral key at once
+        let meta = StealthMetaAddress::<Curve>::new(keypair.public_key, keypair.public_key);
+        let announcement = EphemeralKeypair::<Curve>::from_private_key(largest)
+            .announce(&meta)
+            .unwrap();
+        let stealth_private_key = crate::scan::recover(&announcement, largest, largest).unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            announcement.stealth_commitment
+        );
+    }
+}
@@ -1,23 +1,61 @@
-mod stealth_commitments;
+//! ERC-5564 stealth addresses over pairing-friendly curves.
+//!
+//! # Panics
+//!
+//! Functions that take untrusted input (encoded keys, scalars and announcements, and decoded
+//! announcements handed to scanning and recovery) never panic on malformed data. They reject it
+//! through their `Result` or `Option` instead, so a hostile announcement cannot take a scanner
+//! down. The only documented panics are on caller configuration, such as a zero batch size.
 
+pub mod announcement;
+#[cfg(feature = "async")]
+pub mod async_scan;
+pub mod batch;
+pub mod error;
+pub mod evm_log;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod keypair;
+pub mod meta_address;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod ownership;
+pub mod prelude;
+pub mod scan;
+pub mod scheme;
+pub mod scheme_registry;
+pub mod serialization;
+pub mod stealth_commitments;
+pub mod symmetric;
+pub mod view_tag;
+
+#[cfg(feature = "bls12_377")]
+mod bls12_377_impl;
 #[cfg(feature = "bls12_381")]
 mod bls12_381_impl;
 #[cfg(feature = "bn254")]
-mod bn254_impl;
-#[cfg(feature = "bls12_377")]
-mod bls12_377_impl;
+pub mod bn254_impl;
 
-#[cfg(all(feature = "bls12_381", feature = "bls12_377", feature = "bn254"))]
+#[cfg(any(
+    all(feature = "bn254", feature = "bls12_381"),
+    all(feature = "bn254", feature = "bls12_377"),
+    all(feature = "bls12_381", feature = "bls12_377"),
+))]
 compile_error!("Curves are mutually exclusive and cannot be enabled together");
 
-#[cfg(all(feature = "bls12_377", feature = "bn254"))]
-compile_error!("Curves are mutually exclusive and cannot be enabled together");
-
-#[cfg(all(feature = "bls12_377", feature = "bls12_381"))]
-compile_error!("Curves are mutually exclusive and cannot be enabled together");
-
-#[cfg(all(feature = "bls12_381", feature = "bn254"))]
-compile_error!("Curves are mutually exclusive and cannot be enabled together");
+#[cfg(not(any(feature = "bn254", feature = "bls12_381", feature = "bls12_377")))]
+compile_error!("Enable exactly one curve feature: [bn254, bls12_381, bls12_377]");
 
 #[cfg(feature = "ffi")]
 mod ffi;
+
+#[cfg(test)]
+cfg_if::cfg_if! {
+    if #[cfg(feature = "bls12_381")] {
+        type TestCurve = ark_bls12_381::Bls12_381;
+    } else if #[cfg(feature = "bls12_377")] {
+        type TestCurve = ark_bls12_377::Bls12_377;
+    } else if #[cfg(feature = "bn254")] {
+        type TestCurve = ark_bn254::Bn254;
+    }
+}
@@ -0,0 +1,411 @@
+use crate::announcement::Announcement;
+use crate::error::StealthError;
+use crate::keypair::Keypair;
+use crate::scan::{owns_address, tag_checked_shared_secret};
+use crate::serialization::{
+    deserialize_public_key, public_key_size, serialize_point, serialize_public_key,
+    validate_public_key,
+};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use ark_ec::CurveGroup;
+use ark_std::rand::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Prefix of the ERC-5564 string form of a meta-address, `st:eth:0x<spending><viewing>`.
+pub const META_ADDRESS_PREFIX: &str = "st:eth:0x";
+
+/// Key of the ENS-style text record holding a meta-address in its string form.
+pub const META_ADDRESS_TEXT_RECORD: &str = "st:eth";
+
+/// Size in bytes of the checksum appended by [`StealthMetaAddress::to_checked_bytes`].
+pub const META_ADDRESS_CHECKSUM_SIZE: usize = 4;
+
+/// The first [`META_ADDRESS_CHECKSUM_SIZE`] bytes of `keccak256(payload)`.
+fn checksum(payload: &[u8]) -> [u8; META_ADDRESS_CHECKSUM_SIZE] {
+    let mut hash = [0; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(payload);
+    hasher.finalize(&mut hash);
+    let mut checksum = [0; META_ADDRESS_CHECKSUM_SIZE];
+    checksum.copy_from_slice(&hash[..META_ADDRESS_CHECKSUM_SIZE]);
+    checksum
+}
+
+/// A recipient's published stealth meta-address: the public keys senders need to derive a
+/// stealth commitment for them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StealthMetaAddress<C: StealthAddressOnCurve> {
+    pub spending_public_key: C::Projective,
+    pub viewing_public_key: C::Projective,
+}
+
+impl<C: StealthAddressOnCurve> StealthMetaAddress<C> {
+    pub fn new(spending_public_key: C::Projective, viewing_public_key: C::Projective) -> Self {
+        StealthMetaAddress {
+            spending_public_key,
+            viewing_public_key,
+        }
+    }
+
+    /// The shorthand meta-address using `key` as both the spending and the viewing key, to be
+    /// scanned with [`crate::scan::recover_single_key`]. This gives up the view-only property:
+    /// anyone able to scan for the recipient can also spend their stealth addresses.
+    pub fn single_key(key: &Keypair<C>) -> Result<Self, StealthError> {
+        key.validate()?;
+        validate_public_key(&key.public_key)?;
+        Ok(StealthMetaAddress::new(key.public_key, key.public_key))
+    }
+
+    /// Replaces the viewing key with a fresh one while keeping the spending key, so announcements
+    /// made to the new meta-address cannot be scanned with the old viewing key. Returns the new
+    /// meta-address and its viewing private key.
+    pub fn with_rotated_viewing_key<R: Rng + ?Sized>(&self, rng: &mut R) -> (Self, C::Fr) {
+        let (viewing_key, viewing_public_key) = C::random_keypair_with(rng);
+        (
+            StealthMetaAddress::new(self.spending_public_key, viewing_public_key),
+            viewing_key,
+        )
+    }
+
+    /// Size in bytes of the encoding produced by [`StealthMetaAddress::to_bytes`].
+    pub fn encoded_size() -> usize {
+        2 * public_key_size::<C::Projective>()
+    }
+
+    /// Encodes as `spending_public_key || viewing_public_key` with compressed points, the
+    /// ERC-5564 meta-address layout.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, StealthError> {
+        let mut bytes = serialize_public_key(&self.spending_public_key)?;
+        bytes.extend(serialize_public_key(&self.viewing_public_key)?);
+        Ok(bytes)
+    }
+
+    /// Decodes a meta-address produced by [`StealthMetaAddress::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StealthError> {
+        if bytes.len() != Self::encoded_size() {
+            return Err(StealthError::InvalidLength {
+                expected: Self::encoded_size(),
+                actual: bytes.len(),
+            });
+        }
+        let (spending_public_key, viewing_public_key) =
+            bytes.split_at(public_key_size::<C::Projective>());
+        Ok(StealthMetaAddress {
+            spending_public_key: deserialize_public_key(spending_public_key)?,
+            viewing_public_key: deserialize_public_key(viewing_public_key)?,
+        })
+    }
+
+    /// [`StealthMetaAddress::to_bytes`] followed by a [`META_ADDRESS_CHECKSUM_SIZE`] byte
+    /// checksum, the first bytes of its keccak-256, to catch transcription errors when the
+    /// meta-address is shared out of band. It is not a MAC: anyone can recompute it.
+    pub fn to_checked_bytes(&self) -> Result<Vec<u8>, StealthError> {
+        let mut bytes = self.to_bytes()?;
+        bytes.extend(checksum(&bytes));
+        Ok(bytes)
+    }
+
+    /// Decodes a meta-address produced by [`StealthMetaAddress::to_checked_bytes`], failing with
+    /// [`StealthError::ChecksumMismatch`] if the payload does not match its checksum.
+    pub fn from_checked_bytes(bytes: &[u8]) -> Result<Self, StealthError> {
+        let expected = Self::encoded_size() + META_ADDRESS_CHECKSUM_SIZE;
+        if bytes.len() != expected {
+            return Err(StealthError::InvalidLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let (payload, expected_checksum) = bytes.split_at(Self::encoded_size());
+        if checksum(payload) != expected_checksum {
+            return Err(StealthError::ChecksumMismatch);
+        }
+        Self::from_bytes(payload)
+    }
+
+    /// The keys a scanning service needs to find this recipient's announcements: the viewing
+    /// private key and both public keys, but never the spending private key. Fails with
+    /// [`StealthError::InconsistentKeypair`] if `viewing_private_key` is not this meta-address's
+    /// viewing key.
+    pub fn view_only_export(
+        &self,
+        viewing_private_key: C::Fr,
+    ) -> Result<ViewOnlyKeys<C>, StealthError> {
+        if C::derive_public_key(&viewing_private_key) != self.viewing_public_key {
+            return Err(StealthError::InconsistentKeypair);
+        }
+        Ok(ViewOnlyKeys {
+            viewing_key: viewing_private_key,
+            spending_public_key: self.spending_public_key,
+            viewing_public_key: self.viewing_public_key,
+        })
+    }
+
+    /// Reads the meta-address from resolved text records, under the
+    /// [`META_ADDRESS_TEXT_RECORD`] key.
+    pub fn from_text_records(records: &HashMap<String, String>) -> Result<Self, StealthError> {
+        records
+            .get(META_ADDRESS_TEXT_RECORD)
+            .ok_or(StealthError::MissingTextRecord(META_ADDRESS_TEXT_RECORD))?
+            .parse()
+    }
+}
+
+/// The view-only export of a [`StealthMetaAddress`], from
+/// [`StealthMetaAddress::view_only_export`]. It finds the recipient's stealth addresses but cannot
+/// derive their private keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewOnlyKeys<C: StealthAddressOnCurve> {
+    pub viewing_key: C::Fr,
+    pub spending_public_key: C::Projective,
+    pub viewing_public_key: C::Projective,
+}
+
+impl<C: StealthAddressOnCurve> ViewOnlyKeys<C> {
+    /// Whether `announcement` is addressed to the recipient, as in [`owns_address`].
+    pub fn owns(&self, announcement: &Announcement<C>) -> bool {
+        owns_address::<C>(
+            announcement.stealth_commitment,
+            announcement.ephemeral_public_key,
+            self.viewing_key,
+            self.spending_public_key,
+        )
+    }
+
+    /// The stealth addresses of the announcements addressed to the recipient, in order. The view
+    /// tag is checked first, as in [`crate::scan::recover`].
+    pub fn scan(&self, announcements: &[Announcement<C>]) -> Vec<C::Projective> {
+        announcements
+            .iter()
+            .filter(|announcement| {
+                let shared_point =
+                    C::compute_shared_point(self.viewing_key, announcement.ephemeral_public_key)
+                        .into_affine();
+                tag_checked_shared_secret(announcement, &shared_point).is_some_and(
+                    |shared_secret| {
+                        self.spending_public_key + C::derive_public_key(&shared_secret)
+                            == announcement.stealth_commitment
+                    },
+                )
+            })
+            .map(|announcement| announcement.stealth_commitment)
+            .collect()
+    }
+}
+
+/// Formats as `st:eth:0x` followed by the lowercase hex of [`StealthMetaAddress::to_bytes`].
+impl<C: StealthAddressOnCurve> fmt::Display for StealthMetaAddress<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{META_ADDRESS_PREFIX}{}{}",
+            hex::encode(serialize_point(&self.spending_public_key)),
+            hex::encode(serialize_point(&self.viewing_public_key))
+        )
+    }
+}
+
+impl<C: StealthAddressOnCurve> FromStr for StealthMetaAddress<C> {
+    type Err = StealthError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix(META_ADDRESS_PREFIX)
+            .ok_or(StealthError::InvalidEncoding)?;
+        Self::from_bytes(&hex::decode(digits).map_err(|_| StealthError::InvalidEncoding)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+    use crate::scan::recover;
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::SeedableRng;
+
+    type Curve = crate::TestCurve;
+
+    fn random_meta_address() -> StealthMetaAddress<Curve> {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        StealthMetaAddress::new(spending_public_key, viewing_public_key)
+    }
+
+    #[test]
+    fn test_meta_address_bytes_round_trip() {
+        let meta_address = random_meta_address();
+        let bytes = meta_address.to_bytes().unwrap();
+        assert_eq!(bytes.len(), StealthMetaAddress::<Curve>::encoded_size());
+        assert_eq!(
+            bytes[..public_key_size::<<Curve as StealthAddressOnCurve>::Projective>()],
+            serialize_public_key(&meta_address.spending_public_key).unwrap()
+        );
+        assert_eq!(StealthMetaAddress::from_bytes(&bytes), Ok(meta_address));
+    }
+
+    #[test]
+    fn test_meta_address_rejects_wrong_length() {
+        let bytes = random_meta_address().to_bytes().unwrap();
+        let expected = StealthMetaAddress::<Curve>::encoded_size();
+        for actual in [0, expected - 1, expected + 1] {
+            let mut wrong = bytes.clone();
+            wrong.resize(actual, 0);
+            assert_eq!(
+                StealthMetaAddress::<Curve>::from_bytes(&wrong),
+                Err(StealthError::InvalidLength { expected, actual })
+            );
+        }
+    }
+
+    #[test]
+    fn test_meta_address_checked_bytes() {
+        let meta_address = random_meta_address();
+        let bytes = meta_address.to_checked_bytes().unwrap();
+        assert_eq!(
+            bytes[..StealthMetaAddress::<Curve>::encoded_size()],
+            meta_address.to_bytes().unwrap()
+        );
+        assert_eq!(
+            StealthMetaAddress::from_checked_bytes(&bytes),
+            Ok(meta_address)
+        );
+
+        for index in [0, bytes.len() / 2, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= 0x01;
+            assert_eq!(
+                StealthMetaAddress::<Curve>::from_checked_bytes(&corrupted),
+                Err(StealthError::ChecksumMismatch)
+            );
+        }
+        assert!(matches!(
+            StealthMetaAddress::<Curve>::from_checked_bytes(&bytes[..bytes.len() - 1]),
+            Err(StealthError::InvalidLength { .. })
+        ));
+    }
+
+    #[test]
+    fn test_view_only_keys_find_but_cannot_spend() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let meta_address =
+            StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+        assert_eq!(
+            meta_address.view_only_export(spending_key),
+            Err(StealthError::InconsistentKeypair)
+        );
+        let keys = meta_address.view_only_export(viewing_key).unwrap();
+
+        let (_, other_viewing_public_key) = Curve::random_keypair();
+        let announcements: Vec<_> = [
+            viewing_public_key,
+            other_viewing_public_key,
+            viewing_public_key,
+        ]
+        .into_iter()
+        .map(|viewing_public_key| {
+            generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap()
+        })
+        .collect();
+        assert_eq!(
+            keys.scan(&announcements),
+            [
+                announcements[0].stealth_commitment,
+                announcements[2].stealth_commitment
+            ]
+        );
+        assert!(keys.owns(&announcements[0]));
+        assert!(!keys.owns(&announcements[1]));
+
+        // the viewing key alone recovers nothing spendable
+        assert!(recover(&announcements[0], keys.viewing_key, keys.viewing_key).is_none());
+        assert!(recover(&announcements[0], viewing_key, spending_key).is_some());
+    }
+
+    #[test]
+    fn test_with_rotated_viewing_key() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (old_viewing_key, old_viewing_public_key) = Curve::random_keypair();
+        let meta_address =
+            StealthMetaAddress::<Curve>::new(spending_public_key, old_viewing_public_key);
+
+        let mut rng = StdRng::seed_from_u64(5564);
+        let (rotated, viewing_key) = meta_address.with_rotated_viewing_key(&mut rng);
+        assert_eq!(rotated.spending_public_key, spending_public_key);
+        assert_eq!(
+            rotated.viewing_public_key,
+            Curve::derive_public_key(&viewing_key)
+        );
+        assert_ne!(rotated.viewing_public_key, old_viewing_public_key);
+
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            rotated.viewing_public_key,
+            rotated.spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert!(recover(&announcement, viewing_key, spending_key).is_some());
+        assert!(recover(&announcement, old_viewing_key, spending_key).is_none());
+    }
+
+    #[test]
+    fn test_meta_address_string_round_trip() {
+        let meta_address = random_meta_address();
+        let text = meta_address.to_string();
+        assert!(text.starts_with(META_ADDRESS_PREFIX));
+        assert_eq!(text.parse(), Ok(meta_address));
+        assert_eq!(
+            text.replacen("st:eth:", "st:btc:", 1)
+                .parse::<StealthMetaAddress<Curve>>(),
+            Err(StealthError::InvalidEncoding)
+        );
+        assert!(text[..text.len() - 2]
+            .parse::<StealthMetaAddress<Curve>>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_meta_address_from_text_records() {
+        let meta_address = random_meta_address();
+        let mut records = HashMap::from([("avatar".to_string(), "ipfs://".to_string())]);
+        assert_eq!(
+            StealthMetaAddress::<Curve>::from_text_records(&records),
+            Err(StealthError::MissingTextRecord("st:eth"))
+        );
+
+        records.insert(
+            META_ADDRESS_TEXT_RECORD.to_string(),
+            meta_address.to_string(),
+        );
+        assert_eq!(
+            StealthMetaAddress::from_text_records(&records),
+            Ok(meta_address)
+        );
+    }
+
+    #[test]
+    fn test_single_key_rejects_inconsistent_keypair() {
+        let key = Keypair::<Curve>::random();
+        let meta_address = StealthMetaAddress::single_key(&key).unwrap();
+        assert_eq!(meta_address.spending_public_key, key.public_key);
+        assert_eq!(meta_address.viewing_public_key, key.public_key);
+
+        let (_, other_public_key) = Curve::random_keypair();
+        assert_eq!(
+            StealthMetaAddress::single_key(&Keypair::<Curve>::new(
+                key.private_key,
+                other_public_key
+            )),
+            Err(StealthError::InconsistentKeypair)
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Scanning announcement dumps too large to load, by memory-mapping a file in the
+//! [`serialize_announcements`](crate::announcement::serialize_announcements) format and decoding
+//! one record at a time.
+
+use crate::announcement::{Announcement, ANNOUNCEMENTS_FORMAT_VERSION};
+use crate::error::StealthError;
+use crate::scan::{recover, ScanResult};
+use crate::stealth_commitments::StealthAddressOnCurve;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// Like [`scan_announcements`](crate::scan::scan_announcements), over the announcements stored
+/// at `path`. The file is mapped rather than read, so only the pages being scanned are resident.
+///
+/// The whole file must be a version byte followed by whole records. A truncated trailing record
+/// or any undecodable record fails the scan, rather than silently skipping announcements.
+///
+/// The file must not be modified while it is being scanned.
+pub fn scan_mmap<C: StealthAddressOnCurve>(
+    path: &Path,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Result<Vec<ScanResult<C>>, StealthError> {
+    let file = File::open(path)?;
+    // SAFETY: the map is read only, and callers are told not to modify the file during the scan
+    let map = unsafe { Mmap::map(&file)? };
+    let (version, records) = map.split_first().ok_or(StealthError::InvalidLength {
+        expected: 1,
+        actual: 0,
+    })?;
+    if *version != ANNOUNCEMENTS_FORMAT_VERSION {
+        return Err(StealthError::UnsupportedVersion(*version));
+    }
+    let size = Announcement::<C>::encoded_size();
+    if records.len() % size != 0 {
+        return Err(StealthError::InvalidLength {
+            expected: records.len().next_multiple_of(size),
+            actual: records.len(),
+        });
+    }
+
+    let mut matches = Vec::new();
+    for (index, record) in records.chunks_exact(size).enumerate() {
+        let announcement = Announcement::<C>::from_bytes(record)?;
+        if let Some(stealth_private_key) = recover(&announcement, viewing_key, spending_key) {
+            matches.push(ScanResult {
+                index,
+                stealth_private_key,
+                stealth_address: announcement.stealth_commitment,
+                ephemeral_public_key: announcement.ephemeral_public_key,
+            });
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::{generate_announcement, serialize_announcements};
+    use crate::scan::scan_announcements;
+    use std::io::Write;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_scan_mmap_matches_in_memory_scan() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announcements: Vec<_> = (0..3000)
+            .map(|i| {
+                let recipient = if i % 500 == 7 {
+                    viewing_public_key
+                } else {
+                    other_public_key
+                };
+                generate_announcement::<Curve>(
+                    recipient,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+            })
+            .collect();
+        let bytes = serialize_announcements(&announcements);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&bytes).unwrap();
+        let matches = scan_mmap::<Curve>(file.path(), viewing_key, spending_key).unwrap();
+        assert_eq!(matches.len(), 6);
+        assert_eq!(
+            matches,
+            scan_announcements(&announcements, viewing_key, spending_key)
+        );
+
+        let mut truncated = tempfile::NamedTempFile::new().unwrap();
+        truncated.write_all(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            scan_mmap::<Curve>(truncated.path(), viewing_key, spending_key),
+            Err(StealthError::InvalidLength { .. })
+        ));
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        assert!(scan_mmap::<Curve>(empty.path(), viewing_key, spending_key).is_err());
+        assert_eq!(
+            scan_mmap::<Curve>(
+                &empty.path().with_extension("missing"),
+                viewing_key,
+                spending_key
+            ),
+            Err(StealthError::Io(std::io::ErrorKind::NotFound))
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::announcement::Announcement;
+use crate::error::StealthError;
+use crate::meta_address::StealthMetaAddress;
+use crate::scan::recover;
+use crate::scheme::hash_to_fr_with_domain;
+use crate::serialization::serialize_point;
+use crate::stealth_commitments::StealthAddressOnCurve;
+use ark_ec::{CurveGroup, VariableBaseMSM};
+#[cfg(feature = "os_rng")]
+use ark_std::rand::rngs::OsRng;
+use ark_std::rand::Rng;
+
+const CHALLENGE_DOMAIN: &[u8] = b"erc-5564-ownership";
+
+/// Schnorr proof of knowledge of the private key behind a public key, bound to a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof<C: StealthAddressOnCurve> {
+    pub nonce_commitment: C::Projective,
+    pub response: C::Fr,
+}
+
+pub(crate) fn challenge<C: StealthAddressOnCurve>(
+    nonce_commitment: &C::Projective,
+    public_key: &C::Projective,
+    message: &[u8],
+) -> C::Fr {
+    let mut input = serialize_point(nonce_commitment);
+    input.extend(serialize_point(public_key));
+    input.extend(message);
+    hash_to_fr_with_domain::<C>(CHALLENGE_DOMAIN, &input)
+}
+
+#[cfg(feature = "os_rng")]
+pub fn prove_ownership<C: StealthAddressOnCurve>(
+    private_key: C::Fr,
+    message: &[u8],
+) -> OwnershipProof<C> {
+    prove_ownership_with(private_key, message, &mut OsRng)
+}
+
+pub fn prove_ownership_with<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    private_key: C::Fr,
+    message: &[u8],
+    rng: &mut R,
+) -> OwnershipProof<C> {
+    let (nonce, nonce_commitment) = C::random_keypair_with(rng);
+    let public_key = C::derive_public_key(&private_key);
+    let c = challenge::<C>(&nonce_commitment, &public_key, message);
+    OwnershipProof {
+        nonce_commitment,
+        response: nonce + c * private_key,
+    }
+}
+
+/// Checks `response * G == nonce_commitment + challenge * public_key`.
+pub fn verify_ownership<C: StealthAddressOnCurve>(
+    public_key: C::Projective,
+    message: &[u8],
+    proof: &OwnershipProof<C>,
+) -> bool {
+    let c = challenge::<C>(&proof.nonce_commitment, &public_key, message);
+    C::derive_public_key(&proof.response) == proof.nonce_commitment + public_key * c
+}
+
+/// The message signed for a meta-address: the viewing public key followed by `message`. Signing
+/// with the spending key alone would let whoever relays the meta-address swap in their own
+/// viewing key and read the recipient's payments.
+fn meta_address_message<C: StealthAddressOnCurve>(
+    meta: &StealthMetaAddress<C>,
+    message: &[u8],
+) -> Vec<u8> {
+    let mut input = serialize_point(&meta.viewing_public_key);
+    input.extend(message);
+    input
+}
+
+/// Signs `meta` with its spending key, so senders fetching it from an untrusted source can check
+/// it with [`verify_meta_address_signature`].
+#[cfg(feature = "os_rng")]
+pub fn sign_meta_address<C: StealthAddressOnCurve>(
+    spending_key: C::Fr,
+    meta: &StealthMetaAddress<C>,
+    message: &[u8],
+) -> OwnershipProof<C> {
+    sign_meta_address_with(spending_key, meta, message, &mut OsRng)
+}
+
+pub fn sign_meta_address_with<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    spending_key: C::Fr,
+    meta: &StealthMetaAddress<C>,
+    message: &[u8],
+    rng: &mut R,
+) -> OwnershipProof<C> {
+    prove_ownership_with(spending_key, &meta_address_message(meta, message), rng)
+}
+
+/// Checks that the owner of `meta`'s spending key signed `meta`, including its viewing key, and
+/// `message`.
+pub fn verify_meta_address_signature<C: StealthAddressOnCurve>(
+    meta: &StealthMetaAddress<C>,
+    message: &[u8],
+    sig: &OwnershipProof<C>,
+) -> bool {
+    verify_ownership(
+        meta.spending_public_key,
+        &meta_address_message(meta, message),
+        sig,
+    )
+}
+
+/// Verifies many proofs at once by checking a random linear combination of their verification
+/// equations with a single multi-scalar multiplication. Any invalid proof fails the whole batch.
+#[cfg(feature = "os_rng")]
+pub fn batch_verify_ownership<C: StealthAddressOnCurve>(
+    entries: &[(C::Projective, Vec<u8>, OwnershipProof<C>)],
+) -> bool {
+    batch_verify_ownership_with(entries, &mut OsRng)
+}
+
+pub fn batch_verify_ownership_with<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    entries: &[(C::Projective, Vec<u8>, OwnershipProof<C>)],
+    rng: &mut R,
+) -> bool {
+    let mut bases = Vec::with_capacity(2 * entries.len());
+    let mut scalars = Vec::with_capacity(2 * entries.len());
+    let mut response_sum = C::Fr::from(0u64);
+    for (public_key, message, proof) in entries {
+        let weight = C::generate_random_fr_with(rng);
+        let c = challenge::<C>(&proof.nonce_commitment, public_key, message);
+        response_sum += weight * proof.response;
+        bases.extend([proof.nonce_commitment, *public_key]);
+        scalars.extend([weight, weight * c]);
+    }
+    let bases = C::Projective::normalize_batch(&bases);
+    match C::Projective::msm(&bases, &scalars) {
+        Ok(combined) => C::derive_public_key(&response_sum) == combined,
+        Err(_) => false,
+    }
+}
+
+/// Recovers the stealth private key for `announcement` and immediately signs `message` with it,
+/// authorizing a spend from the stealth address.
+#[cfg(feature = "os_rng")]
+pub fn recover_and_sign<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    message: &[u8],
+) -> Result<(C::Fr, OwnershipProof<C>), StealthError> {
+    recover_and_sign_with(announcement, viewing_key, spending_key, message, &mut OsRng)
+}
+
+pub fn recover_and_sign_with<C: StealthAddressOnCurve, R: Rng + ?Sized>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    message: &[u8],
+    rng: &mut R,
+) -> Result<(C::Fr, OwnershipProof<C>), StealthError> {
+    let stealth_private_key =
+        recover(announcement, viewing_key, spending_key).ok_or(StealthError::NotRecipient)?;
+    let proof = prove_ownership_with(stealth_private_key, message, rng);
+    Ok((stealth_private_key, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::SeedableRng;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_ownership_proof() {
+        let (private_key, public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let proof = prove_ownership::<Curve>(private_key, b"message");
+
+        assert!(verify_ownership(public_key, b"message", &proof));
+        assert!(!verify_ownership(public_key, b"other message", &proof));
+        assert!(!verify_ownership(other_public_key, b"message", &proof));
+    }
+
+    #[test]
+    fn test_batch_verify_ownership() {
+        let mut entries: Vec<_> = (0..4u8)
+            .map(|i| {
+                let (private_key, public_key) = Curve::random_keypair();
+                let message = vec![i; 8];
+                let proof = prove_ownership::<Curve>(private_key, &message);
+                (public_key, message, proof)
+            })
+            .collect();
+        assert!(batch_verify_ownership(&entries));
+        assert!(batch_verify_ownership::<Curve>(&[]));
+
+        entries[2].1 = b"tampered".to_vec();
+        assert!(!batch_verify_ownership(&entries));
+    }
+
+    #[test]
+    fn test_recover_and_sign() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        let (stealth_private_key, proof) =
+            recover_and_sign(&announcement, viewing_key, spending_key, b"spend").unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            announcement.stealth_commitment
+        );
+        assert!(verify_ownership(
+            announcement.stealth_commitment,
+            b"spend",
+            &proof
+        ));
+
+        let (other_spending_key, _) = Curve::random_keypair();
+        assert_eq!(
+            recover_and_sign(&announcement, viewing_key, other_spending_key, b"spend"),
+            Err(StealthError::NotRecipient)
+        );
+    }
+
+    #[test]
+    fn test_verify_meta_address_signature() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (other_key, other_public_key) = Curve::random_keypair();
+        let meta = StealthMetaAddress::<Curve>::new(spending_public_key, viewing_public_key);
+        let mut rng = StdRng::seed_from_u64(5564);
+
+        let sig = sign_meta_address_with(spending_key, &meta, b"ens:alice", &mut rng);
+        assert!(verify_meta_address_signature(&meta, b"ens:alice", &sig));
+        assert!(!verify_meta_address_signature(&meta, b"ens:bob", &sig));
+
+        let wrong_key = sign_meta_address_with(other_key, &meta, b"ens:alice", &mut rng);
+        assert!(!verify_meta_address_signature(
+            &meta,
+            b"ens:alice",
+            &wrong_key
+        ));
+
+        let swapped_viewing_key = StealthMetaAddress::new(spending_public_key, other_public_key);
+        assert!(!verify_meta_address_signature(
+            &swapped_viewing_key,
+            b"ens:alice",
+            &sig
+        ));
+    }
+}
@@ -0,0 +1,45 @@
+//! The types and functions most integrations need.
+//!
+//! ```
+//! use erc_5564_rs::prelude::*;
+//!
+//! fn send_and_receive<C: StealthAddressOnCurve>() -> Result<(), StealthError> {
+//!     let spending = Keypair::<C>::random();
+//!     let viewing = Keypair::<C>::random();
+//!
+//!     // the sender
+//!     let (ephemeral_private_key, _) = C::random_keypair();
+//!     let announcement = generate_announcement::<C>(
+//!         viewing.public_key,
+//!         spending.public_key,
+//!         ephemeral_private_key,
+//!     ).unwrap();
+//!     let published = announcement.to_bytes();
+//!
+//!     // the recipient
+//!     let announcements = [Announcement::<C>::from_bytes(&published)?];
+//!     let results: Vec<ScanResult<C>> =
+//!         scan_announcements(&announcements, viewing.private_key, spending.private_key);
+//!     assert_eq!(results.len(), 1);
+//!     assert_eq!(
+//!         C::derive_public_key(&results[0].stealth_private_key),
+//!         announcement.stealth_commitment
+//!     );
+//!     Ok(())
+//! }
+//!
+//! # #[cfg(feature = "bn254")]
+//! send_and_receive::<ark_bn254::Bn254>().unwrap();
+//! # #[cfg(feature = "bls12_381")]
+//! send_and_receive::<ark_bls12_381::Bls12_381>().unwrap();
+//! # #[cfg(feature = "bls12_377")]
+//! send_and_receive::<ark_bls12_377::Bls12_377>().unwrap();
+//! ```
+
+pub use crate::announcement::{generate_announcement, Announcement};
+pub use crate::error::StealthError;
+pub use crate::keypair::Keypair;
+pub use crate::meta_address::StealthMetaAddress;
+pub use crate::scan::{recover, scan_announcements, ScanResult};
+pub use crate::serialization::{deserialize_public_key, serialize_public_key};
+pub use crate::stealth_commitments::StealthAddressOnCurve;
@@ -0,0 +1,1529 @@
+use crate::announcement::Announcement;
+use crate::batch::{compute_shared_points_batch, normalize_batch};
+use crate::error::StealthError;
+use crate::serialization::serialize_point;
+use crate::stealth_commitments::{
+    constant_time_add, shared_secret_hash_preimage, StealthAddressOnCurve,
+};
+use crate::view_tag::{
+    anonymity_reduction, is_valid_view_tag_width, view_tag_from_scalar, view_tag_mask,
+    VIEW_TAG_BYTE_WIDTH,
+};
+use ark_ec::{AffineRepr, CurveGroup};
+use num_traits::Zero;
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroizing;
+
+/// Recovers the stealth private key for an announcement, if it was addressed to these keys.
+/// The view tag is checked first, and the derived key is then checked against the commitment.
+pub fn recover<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Option<C::Fr> {
+    let shared_point =
+        C::compute_shared_point(viewing_key, announcement.ephemeral_public_key).into_affine();
+    let shared_secret = tag_checked_shared_secret(announcement, &shared_point)?;
+    let stealth_private_key = constant_time_add(spending_key, shared_secret);
+    if C::derive_public_key(&stealth_private_key) == announcement.stealth_commitment {
+        Some(stealth_private_key)
+    } else {
+        None
+    }
+}
+
+/// The view tag check every recovery path shares: hashes the shared point of the viewing key and
+/// the ephemeral public key, and returns the shared secret if its tag agrees with the announced
+/// one in the announcement's [`Announcement::view_tag_width`] bytes. An identity shared point can
+/// only come from malformed data, and matches nothing.
+pub(crate) fn tag_checked_shared_secret<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    shared_point: &<C::Projective as CurveGroup>::Affine,
+) -> Option<C::Fr> {
+    if shared_point.is_zero() {
+        return None;
+    }
+    #[cfg(feature = "tracing")]
+    let span =
+        tracing::trace_span!("view_tag_check", tag_matched = tracing::field::Empty).entered();
+    let shared_secret = C::hash_to_fr(&shared_secret_hash_preimage(shared_point));
+    let tag_matched = announcement.matches_view_tag(view_tag_from_scalar(&shared_secret));
+    #[cfg(feature = "tracing")]
+    span.record("tag_matched", tag_matched);
+    tag_matched.then_some(shared_secret)
+}
+
+/// [`recover`] for a [`crate::meta_address::StealthMetaAddress::single_key`] recipient, whose
+/// one key is both the viewing and the spending key.
+pub fn recover_single_key<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    key: C::Fr,
+) -> Option<C::Fr> {
+    recover(announcement, key, key)
+}
+
+/// Like [`recover`], for senders and recipients that agreed on a view tag narrower than
+/// [`crate::view_tag::VIEW_TAG_BYTE_WIDTH`] bytes. Rejects announcements made with a width other
+/// than `tag_byte_width` with [`StealthError::ViewTagWidthMismatch`], and announcements for other
+/// recipients with [`StealthError::NotRecipient`]. An ephemeral public key whose shared point
+/// with the viewing key is the identity can only come from malformed data, and is rejected with
+/// [`StealthError::DegenerateSharedPoint`] before any hashing.
+pub fn recover_with_tag_width<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    tag_byte_width: usize,
+) -> Result<C::Fr, StealthError> {
+    if announcement.view_tag_width != tag_byte_width {
+        return Err(StealthError::ViewTagWidthMismatch {
+            announced: announcement.view_tag_width,
+            configured: tag_byte_width,
+        });
+    }
+    let shared_point = C::compute_shared_point(viewing_key, announcement.ephemeral_public_key);
+    if shared_point.is_zero() {
+        return Err(StealthError::DegenerateSharedPoint);
+    }
+    let shared_secret = C::hash_to_fr(&shared_secret_hash_preimage(&shared_point));
+    let mask = view_tag_mask(tag_byte_width);
+    if view_tag_from_scalar(&shared_secret) & mask != announcement.view_tag & mask {
+        return Err(StealthError::NotRecipient);
+    }
+    let stealth_private_key = constant_time_add(spending_key, shared_secret);
+    if C::derive_public_key(&stealth_private_key) == announcement.stealth_commitment {
+        Ok(stealth_private_key)
+    } else {
+        Err(StealthError::NotRecipient)
+    }
+}
+
+/// Like [`recover`], but converts the stealth private key into the caller's signing key type.
+///
+/// `K::from` receives the stealth private key exactly as it would sign for the stealth address,
+/// i.e. the key whose public key is the announcement's stealth commitment. Implementations must
+/// not reduce, hash or otherwise transform the scalar, or signatures will not verify against the
+/// stealth address.
+pub fn recover_as<C: StealthAddressOnCurve, K: From<C::Fr>>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Option<K> {
+    recover(announcement, viewing_key, spending_key).map(K::from)
+}
+
+/// Whether both announcements are addressed to these keys, e.g. to consolidate received payments.
+/// Without the recipient's keys the two announcements are unlinkable.
+pub fn same_recipient<C: StealthAddressOnCurve>(
+    a: &Announcement<C>,
+    b: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> bool {
+    recover(a, viewing_key, spending_key).is_some()
+        && recover(b, viewing_key, spending_key).is_some()
+}
+
+/// Whether `stealth_address` belongs to the recipient with these keys, using only the viewing key
+/// and the public spending key, so light clients can recognise their payments without holding the
+/// spending key. Unlike [`recover`] this skips the view tag, which only speeds up scanning. An
+/// ephemeral public key whose shared point is the identity owns nothing.
+pub fn owns_address<C: StealthAddressOnCurve>(
+    stealth_address: C::Projective,
+    ephemeral_public_key: C::Projective,
+    viewing_key: C::Fr,
+    spending_public_key: C::Projective,
+) -> bool {
+    let shared_point = C::compute_shared_point(viewing_key, ephemeral_public_key);
+    if shared_point.is_zero() {
+        return false;
+    }
+    let shared_secret = C::hash_to_fr(&shared_secret_hash_preimage(&shared_point));
+    spending_public_key + C::derive_public_key(&shared_secret) == stealth_address
+}
+
+/// Why [`diagnose_match`] did or did not recover an announcement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchDiagnosis<C: StealthAddressOnCurve> {
+    Matched,
+    /// The shared point of the viewing key and the ephemeral public key is the identity, which
+    /// only malformed data produces, so the announcement is rejected before any hashing.
+    DegenerateSharedPoint,
+    /// The view tag derived from the shared secret differs from the announced one, usually
+    /// because the viewing key is wrong or the sender used a different hash.
+    TagMismatch {
+        expected: u64,
+        computed: u64,
+    },
+    /// The view tag matched but the stealth commitment did not, usually because the spending key
+    /// is wrong or the view tag matched by chance.
+    CommitmentMismatch {
+        recomputed: C::Projective,
+    },
+}
+
+/// Debug aid explaining why [`recover`] does or does not match an announcement.
+pub fn diagnose_match<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> MatchDiagnosis<C> {
+    let shared_point = C::compute_shared_point(viewing_key, announcement.ephemeral_public_key);
+    if shared_point.is_zero() {
+        return MatchDiagnosis::DegenerateSharedPoint;
+    }
+    let shared_secret = C::hash_to_fr(&shared_secret_hash_preimage(&shared_point));
+    let computed = view_tag_from_scalar(&shared_secret);
+    if !announcement.matches_view_tag(computed) {
+        return MatchDiagnosis::TagMismatch {
+            expected: announcement.view_tag,
+            computed,
+        };
+    }
+
+    let recomputed = C::derive_public_key(&constant_time_add(spending_key, shared_secret));
+    if recomputed == announcement.stealth_commitment {
+        MatchDiagnosis::Matched
+    } else {
+        MatchDiagnosis::CommitmentMismatch { recomputed }
+    }
+}
+
+/// A matched announcement with everything needed to spend from its stealth address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanResult<C: StealthAddressOnCurve> {
+    /// Position of the announcement in the scanned input.
+    pub index: usize,
+    pub stealth_private_key: C::Fr,
+    pub stealth_address: C::Projective,
+    pub ephemeral_public_key: C::Projective,
+}
+
+/// An announcement consumed by [`Announcement::into_recovered`], with everything needed to spend
+/// from its stealth address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredPayment<C: StealthAddressOnCurve> {
+    pub stealth_private_key: C::Fr,
+    pub stealth_address: C::Projective,
+    pub metadata: Vec<u8>,
+}
+
+impl<C: StealthAddressOnCurve> Announcement<C> {
+    /// Like [`recover`], but consumes the announcement, moving its metadata into the payment
+    /// rather than copying it.
+    pub fn into_recovered(
+        self,
+        viewing_key: C::Fr,
+        spending_key: C::Fr,
+    ) -> Option<RecoveredPayment<C>> {
+        let stealth_private_key = recover(&self, viewing_key, spending_key)?;
+        Some(RecoveredPayment {
+            stealth_private_key,
+            stealth_address: self.stealth_commitment,
+            metadata: self.metadata,
+        })
+    }
+}
+
+/// [`scan_announcements`] with the shared points of the whole batch computed by
+/// [`compute_shared_points_batch`] and normalized to affine together, rather than once per
+/// announcement inside the shared secret hash.
+fn scan_normalized<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Vec<ScanResult<C>> {
+    let ephemeral_public_keys: Vec<_> = announcements
+        .iter()
+        .map(|announcement| announcement.ephemeral_public_key)
+        .collect();
+    let shared_points = normalize_batch::<C>(&compute_shared_points_batch::<C>(
+        viewing_key,
+        &ephemeral_public_keys,
+    ));
+    announcements
+        .iter()
+        .zip(shared_points)
+        .enumerate()
+        .filter_map(|(index, (announcement, shared_point))| {
+            let q_hashed = tag_checked_shared_secret(announcement, &shared_point)?;
+            let stealth_private_key = constant_time_add(spending_key, q_hashed);
+            (C::derive_public_key(&stealth_private_key) == announcement.stealth_commitment)
+                .then_some(ScanResult {
+                    index,
+                    stealth_private_key,
+                    stealth_address: announcement.stealth_commitment,
+                    ephemeral_public_key: announcement.ephemeral_public_key,
+                })
+        })
+        .collect()
+}
+
+/// Calls `f` with a [`ScanResult`] for every announcement addressed to these keys, without
+/// collecting the matches.
+pub fn for_each_match<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    mut f: impl FnMut(ScanResult<C>),
+) {
+    for (index, announcement) in announcements.iter().enumerate() {
+        if let Some(stealth_private_key) = recover(announcement, viewing_key, spending_key) {
+            f(ScanResult {
+                index,
+                stealth_private_key,
+                stealth_address: announcement.stealth_commitment,
+                ephemeral_public_key: announcement.ephemeral_public_key,
+            });
+        }
+    }
+}
+
+/// Returns a [`ScanResult`] for every announcement addressed to these keys.
+pub fn scan_announcements<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Vec<ScanResult<C>> {
+    // every announcement costs at most one view tag check, none if its shared point is the
+    // identity
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "scan_announcements",
+        tag_checks = announcements.len(),
+        matches = tracing::field::Empty
+    )
+    .entered();
+    let mut matches = Vec::new();
+    for_each_match(announcements, viewing_key, spending_key, |result| {
+        matches.push(result)
+    });
+    #[cfg(feature = "tracing")]
+    span.record("matches", matches.len());
+    matches
+}
+
+/// Summary of a [`scan_report`] over a set of announcements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanReport<C: StealthAddressOnCurve> {
+    pub matched: Vec<ScanResult<C>>,
+    /// Announcements whose view tag matched but whose stealth commitment did not, i.e. tag
+    /// collisions caught by the commitment check.
+    pub tag_false_positives: usize,
+    pub total: usize,
+    /// How many announcements had their tag checked at each width, `tag_widths[w - 1]` for a
+    /// `w` byte tag. Announcements with an invalid width are not checked and not counted.
+    pub tag_widths: [usize; VIEW_TAG_BYTE_WIDTH],
+}
+
+impl<C: StealthAddressOnCurve> ScanReport<C> {
+    /// The mean [`anonymity_reduction`] of the tags [`scan_report`] checked, each at its
+    /// announcement's own width, or that of a full width tag if none was checked.
+    pub fn anonymity_reduction(&self) -> f64 {
+        let checked: usize = self.tag_widths.iter().sum();
+        if checked == 0 {
+            return anonymity_reduction(VIEW_TAG_BYTE_WIDTH);
+        }
+        self.expected_tag_collisions() / checked as f64
+    }
+
+    /// How many of the checked announcements are expected to share a given tag, summing the
+    /// [`anonymity_reduction`] of each at its width, i.e. the [`ScanReport::tag_false_positives`]
+    /// expected for an honest tag.
+    pub fn expected_tag_collisions(&self) -> f64 {
+        self.tag_widths
+            .iter()
+            .enumerate()
+            .map(|(width, count)| *count as f64 * anonymity_reduction(width + 1))
+            .sum()
+    }
+}
+
+/// Like [`scan_announcements`], but also counts the view tag false positives, for monitoring the
+/// tag collision rate.
+pub fn scan_report<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> ScanReport<C> {
+    let mut report = ScanReport {
+        matched: Vec::new(),
+        tag_false_positives: 0,
+        total: announcements.len(),
+        tag_widths: [0; VIEW_TAG_BYTE_WIDTH],
+    };
+    for (index, announcement) in announcements.iter().enumerate() {
+        if is_valid_view_tag_width(announcement.view_tag_width) {
+            report.tag_widths[announcement.view_tag_width - 1] += 1;
+        }
+        // the same checks as recover, split so a tag match with a wrong commitment is counted
+        let shared_point =
+            C::compute_shared_point(viewing_key, announcement.ephemeral_public_key).into_affine();
+        let Some(shared_secret) = tag_checked_shared_secret(announcement, &shared_point) else {
+            continue;
+        };
+        let stealth_private_key = constant_time_add(spending_key, shared_secret);
+        if C::derive_public_key(&stealth_private_key) == announcement.stealth_commitment {
+            report.matched.push(ScanResult {
+                index,
+                stealth_private_key,
+                stealth_address: announcement.stealth_commitment,
+                ephemeral_public_key: announcement.ephemeral_public_key,
+            });
+        } else {
+            report.tag_false_positives += 1;
+        }
+    }
+    report
+}
+
+/// Like [`scan_announcements`], but ordered by the compressed ephemeral public key, then the
+/// compressed stealth address, so the output is the same whatever the input order. Each
+/// [`ScanResult::index`] still points into `announcements`.
+pub fn scan_sorted<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Vec<ScanResult<C>> {
+    let mut matches = scan_announcements(announcements, viewing_key, spending_key);
+    matches.sort_by_cached_key(|result| {
+        (
+            serialize_point(&result.ephemeral_public_key),
+            serialize_point(&result.stealth_address),
+        )
+    });
+    matches
+}
+
+/// Merges the results of scanning two overlapping announcement feeds, keeping `a` then `b` in
+/// order and dropping every result whose compressed stealth address was already seen. Each
+/// [`ScanResult::index`] still points into the feed the result came from.
+pub fn merge_scan_results<C: StealthAddressOnCurve>(
+    a: Vec<ScanResult<C>>,
+    b: Vec<ScanResult<C>>,
+) -> Vec<ScanResult<C>> {
+    let mut seen = HashSet::new();
+    a.into_iter()
+        .chain(b)
+        .filter(|result| seen.insert(serialize_point(&result.stealth_address)))
+        .collect()
+}
+
+/// Like [`scan_announcements`], skipping announcements from block `reorg_from` onwards after a
+/// reorg. Announcements without a block number are kept. The skipped announcements are not
+/// recovered at all, and the indices still refer to `announcements`.
+pub fn scan_with_reorg<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    reorg_from: u64,
+) -> Vec<ScanResult<C>> {
+    announcements
+        .iter()
+        .enumerate()
+        .filter(|(_, announcement)| {
+            announcement
+                .block_number
+                .is_none_or(|block_number| block_number < reorg_from)
+        })
+        .filter_map(|(index, announcement)| {
+            recover(announcement, viewing_key, spending_key).map(|stealth_private_key| ScanResult {
+                index,
+                stealth_private_key,
+                stealth_address: announcement.stealth_commitment,
+                ephemeral_public_key: announcement.ephemeral_public_key,
+            })
+        })
+        .collect()
+}
+
+/// Recovers every announcement addressed to these keys, keyed by the compressed stealth address
+/// so a later spend from it can be matched in O(1). Should two announcements share a stealth
+/// address, the last one wins.
+pub fn recover_map<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> HashMap<Vec<u8>, C::Fr> {
+    let mut keys = HashMap::new();
+    for_each_match(announcements, viewing_key, spending_key, |result| {
+        keys.insert(
+            serialize_point(&result.stealth_address),
+            result.stealth_private_key,
+        );
+    });
+    keys
+}
+
+/// Like [`scan_announcements`], but returns only the announcement index and stealth private key,
+/// with the key wiped from memory when the caller drops it.
+#[cfg(feature = "zeroize")]
+pub fn scan_announcements_zeroizing<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Vec<(usize, Zeroizing<C::Fr>)> {
+    let mut matches = Vec::new();
+    for_each_match(announcements, viewing_key, spending_key, |result| {
+        matches.push((result.index, Zeroizing::new(result.stealth_private_key)))
+    });
+    matches
+}
+
+/// Like [`scan_announcements`], for wallets that diversify their spending key per receiving
+/// index: the announcement at index `i` is recovered with `spending_key_fn(i)`.
+pub fn scan_diversified<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key_fn: impl Fn(usize) -> C::Fr,
+) -> Vec<ScanResult<C>> {
+    announcements
+        .iter()
+        .enumerate()
+        .filter_map(|(index, announcement)| {
+            recover(announcement, viewing_key, spending_key_fn(index)).map(|stealth_private_key| {
+                ScanResult {
+                    index,
+                    stealth_private_key,
+                    stealth_address: announcement.stealth_commitment,
+                    ephemeral_public_key: announcement.ephemeral_public_key,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Scans one announcement feed for several recipients, given as `(viewing_key, spending_key)`
+/// pairs. Returns `(announcement index, recipient index, stealth private key)` for every match,
+/// ordered by announcement and then recipient.
+pub fn scan_multi_recipient<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    recipients: &[(C::Fr, C::Fr)],
+) -> Vec<(usize, usize, C::Fr)> {
+    let mut matches = Vec::new();
+    for (index, announcement) in announcements.iter().enumerate() {
+        for (recipient, (viewing_key, spending_key)) in recipients.iter().enumerate() {
+            if let Some(stealth_private_key) = recover(announcement, *viewing_key, *spending_key) {
+                matches.push((index, recipient, stealth_private_key));
+            }
+        }
+    }
+    matches
+}
+
+/// Announcements scanned to calibrate [`estimate_scan_duration`].
+const CALIBRATION_ANNOUNCEMENTS: usize = 32;
+
+/// Order-of-magnitude estimate of how long [`scan_announcements`] takes over `count`
+/// announcements, e.g. for a progress bar. The per-announcement cost is measured with a short
+/// scan on the first call for each curve and cached for the life of the process.
+pub fn estimate_scan_duration<C: StealthAddressOnCurve + 'static>(count: usize) -> Duration {
+    static PER_ANNOUNCEMENT: OnceLock<Mutex<HashMap<TypeId, Duration>>> = OnceLock::new();
+    let mut costs = PER_ANNOUNCEMENT
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let per_announcement = *costs.entry(TypeId::of::<C>()).or_insert_with(|| {
+        // almost every scanned announcement is someone else's, so time the tag mismatch path
+        let point = C::derive_public_key(&C::Fr::from(2u64));
+        let announcements: Vec<_> = (0..CALIBRATION_ANNOUNCEMENTS as u64)
+            .map(|view_tag| Announcement::<C>::new(point, point, view_tag))
+            .collect();
+        let start = Instant::now();
+        scan_announcements(&announcements, C::Fr::from(3u64), C::Fr::from(5u64));
+        start.elapsed() / CALIBRATION_ANNOUNCEMENTS as u32
+    });
+    per_announcement.saturating_mul(u32::try_from(count).unwrap_or(u32::MAX))
+}
+
+/// Scans `batch_size` announcements at a time, handing each batch's matches to `on_batch`
+/// before moving on, so callers can yield to other work in between.
+/// Indices are into the full `announcements` slice.
+///
+/// # Panics
+///
+/// Panics if `batch_size` is 0.
+pub fn scan_in_batches<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+    batch_size: usize,
+    mut on_batch: impl FnMut(&[ScanResult<C>]),
+) {
+    assert!(batch_size > 0, "batch_size must be non-zero");
+    for (batch_index, batch) in announcements.chunks(batch_size).enumerate() {
+        let offset = batch_index * batch_size;
+        let mut matches = scan_normalized(batch, viewing_key, spending_key);
+        for result in &mut matches {
+            result.index += offset;
+        }
+        on_batch(&matches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::seq::SliceRandom;
+    use ark_std::rand::SeedableRng;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_merge_scan_results() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let announcements: Vec<_> = (0..5)
+            .map(|_| {
+                generate_announcement::<Curve>(
+                    viewing_public_key,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        // the feeds share announcements 2 and 3, at different positions
+        let a = scan_announcements(&announcements[..4], viewing_key, spending_key);
+        let b = scan_announcements(&announcements[2..], viewing_key, spending_key);
+        let merged = merge_scan_results(a.clone(), b.clone());
+        assert_eq!(merged.len(), 5);
+        assert_eq!(merged[..4], a);
+        assert_eq!(merged[4], b[2]);
+        let addresses: HashSet<_> = merged
+            .iter()
+            .map(|result| serialize_point(&result.stealth_address))
+            .collect();
+        assert_eq!(addresses.len(), merged.len());
+        assert_eq!(merge_scan_results(a.clone(), a.clone()), a);
+    }
+
+    #[test]
+    fn test_into_recovered() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap()
+        .with_metadata(vec![1, 2, 3]);
+
+        let payment = announcement
+            .clone()
+            .into_recovered(viewing_key, spending_key)
+            .unwrap();
+        assert_eq!(payment.stealth_address, announcement.stealth_commitment);
+        assert_eq!(
+            Curve::derive_public_key(&payment.stealth_private_key),
+            payment.stealth_address
+        );
+        assert_eq!(payment.metadata, [1, 2, 3]);
+        assert_eq!(announcement.into_recovered(spending_key, viewing_key), None);
+    }
+
+    #[test]
+    fn test_scan_in_batches_matches_full_scan() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_spending_public_key) = Curve::random_keypair();
+        let (_, other_viewing_public_key) = Curve::random_keypair();
+
+        // every third announcement is ours
+        let announcements: Vec<Announcement<Curve>> = (0..10)
+            .map(|i| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if i % 3 == 0 {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                } else {
+                    generate_announcement(
+                        other_viewing_public_key,
+                        other_spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                }
+            })
+            .collect();
+
+        let full = scan_announcements(&announcements, viewing_key, spending_key);
+        assert_eq!(
+            full.iter().map(|result| result.index).collect::<Vec<_>>(),
+            vec![0, 3, 6, 9]
+        );
+
+        let mut batched = Vec::new();
+        let mut batches = 0;
+        scan_in_batches(&announcements, viewing_key, spending_key, 4, |matches| {
+            batches += 1;
+            batched.extend_from_slice(matches);
+        });
+        assert_eq!(batches, 3);
+        assert_eq!(batched, full);
+    }
+
+    #[test]
+    fn test_scan_result_fields() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let (other_ephemeral_private_key, _) = Curve::random_keypair();
+
+        let announcements = vec![
+            generate_announcement::<Curve>(
+                other_public_key,
+                other_public_key,
+                other_ephemeral_private_key,
+            )
+            .unwrap(),
+            generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap(),
+        ];
+        let results = scan_announcements(&announcements, viewing_key, spending_key);
+        assert_eq!(results.len(), 1);
+
+        let result = &results[0];
+        let expected_private_key = Curve::generate_stealth_private_key(
+            ephemeral_public_key,
+            viewing_key,
+            spending_key,
+            announcements[1].view_tag,
+        )
+        .unwrap();
+        assert_eq!(result.index, 1);
+        assert_eq!(result.stealth_private_key, expected_private_key);
+        assert_eq!(result.stealth_address, announcements[1].stealth_commitment);
+        assert_eq!(
+            Curve::derive_public_key(&result.stealth_private_key),
+            result.stealth_address
+        );
+        assert_eq!(result.ephemeral_public_key, ephemeral_public_key);
+    }
+
+    #[test]
+    fn test_same_recipient() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announce = |viewing_public_key, spending_public_key| {
+            let (ephemeral_private_key, _) = Curve::random_keypair();
+            generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap()
+        };
+
+        let first = announce(viewing_public_key, spending_public_key);
+        let second = announce(viewing_public_key, spending_public_key);
+        let other = announce(other_public_key, other_public_key);
+
+        assert!(same_recipient(&first, &second, viewing_key, spending_key));
+        assert!(!same_recipient(&first, &other, viewing_key, spending_key));
+        assert!(!same_recipient(&other, &second, viewing_key, spending_key));
+    }
+
+    #[test]
+    fn test_for_each_match_visits_scan_results() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+
+        let announcements: Vec<Announcement<Curve>> = (0..6)
+            .map(|i| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if i % 2 == 1 {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                } else {
+                    generate_announcement(other_public_key, other_public_key, ephemeral_private_key)
+                        .unwrap()
+                }
+            })
+            .collect();
+
+        let mut visited = Vec::new();
+        for_each_match(&announcements, viewing_key, spending_key, |result| {
+            visited.push(result)
+        });
+        assert_eq!(
+            visited
+                .iter()
+                .map(|result| result.index)
+                .collect::<Vec<_>>(),
+            vec![1, 3, 5]
+        );
+        assert_eq!(
+            visited,
+            scan_announcements(&announcements, viewing_key, spending_key)
+        );
+    }
+
+    #[test]
+    fn test_recover_as_signing_key() {
+        #[derive(Debug, PartialEq)]
+        struct SigningKey(<Curve as StealthAddressOnCurve>::Fr);
+
+        impl From<<Curve as StealthAddressOnCurve>::Fr> for SigningKey {
+            fn from(key: <Curve as StealthAddressOnCurve>::Fr) -> Self {
+                SigningKey(key)
+            }
+        }
+
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        let signing_key: SigningKey = recover_as(&announcement, viewing_key, spending_key).unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&signing_key.0),
+            announcement.stealth_commitment
+        );
+        assert_eq!(
+            recover_as::<Curve, SigningKey>(&announcement, spending_key, viewing_key),
+            None
+        );
+    }
+
+    #[test]
+    fn test_wrong_recipient_cannot_recover() {
+        let (_, alice_spending_public_key) = Curve::random_keypair();
+        let (_, alice_viewing_public_key) = Curve::random_keypair();
+        let (bob_spending_key, _) = Curve::random_keypair();
+        let (bob_viewing_key, _) = Curve::random_keypair();
+
+        // one byte tags collide for about 1 in 256 announcements, which then reach the commitment
+        // check; stop at the first, which 4096 rounds miss with probability below 1e-7
+        let mut tag_collisions = 0;
+        for _ in 0..4096 {
+            let announcement = generate_announcement::<Curve>(
+                alice_viewing_public_key,
+                alice_spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap()
+            .with_view_tag_width(1);
+            assert_eq!(
+                recover(&announcement, bob_viewing_key, bob_spending_key),
+                None
+            );
+            match diagnose_match(&announcement, bob_viewing_key, bob_spending_key) {
+                MatchDiagnosis::TagMismatch { .. } => {}
+                MatchDiagnosis::CommitmentMismatch { recomputed } => {
+                    assert_ne!(recomputed, announcement.stealth_commitment);
+                    tag_collisions += 1;
+                    break;
+                }
+                diagnosis => panic!("bob recovered alice's announcement: {diagnosis:?}"),
+            }
+        }
+        assert_eq!(
+            tag_collisions, 1,
+            "no tag collision reached the commitment check"
+        );
+    }
+
+    #[test]
+    fn test_diagnose_match() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        assert_eq!(
+            diagnose_match(&announcement, viewing_key, spending_key),
+            MatchDiagnosis::Matched
+        );
+
+        match diagnose_match(&announcement, other_key, spending_key) {
+            MatchDiagnosis::TagMismatch { expected, computed } => {
+                assert_eq!(expected, announcement.view_tag);
+                assert_ne!(computed, expected);
+            }
+            diagnosis => panic!("expected a tag mismatch, got {diagnosis:?}"),
+        }
+
+        match diagnose_match(&announcement, viewing_key, other_key) {
+            MatchDiagnosis::CommitmentMismatch { recomputed } => {
+                assert_ne!(recomputed, announcement.stealth_commitment);
+                assert_eq!(
+                    recomputed,
+                    Curve::derive_public_key(
+                        &recover(&announcement, viewing_key, spending_key).unwrap()
+                    ) - spending_public_key
+                        + Curve::derive_public_key(&other_key)
+                );
+            }
+            diagnosis => panic!("expected a commitment mismatch, got {diagnosis:?}"),
+        }
+    }
+
+    #[test]
+    fn test_scan_diversified() {
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let spending_keys: Vec<_> = (0..4).map(|_| Curve::random_keypair()).collect();
+
+        // announcement i pays spending key min(i + 1, 3)
+        let announcements: Vec<Announcement<Curve>> = [1, 2, 3, 3]
+            .into_iter()
+            .map(|key_index| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                generate_announcement(
+                    viewing_public_key,
+                    spending_keys[key_index].1,
+                    ephemeral_private_key,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let results = scan_diversified(&announcements, viewing_key, |index| spending_keys[index].0);
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.index)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+
+        let results = scan_diversified(&announcements, viewing_key, |index| {
+            spending_keys[(index + 1).min(3)].0
+        });
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.index)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        for result in &results {
+            assert_eq!(
+                Curve::derive_public_key(&result.stealth_private_key),
+                result.stealth_address
+            );
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_scan_announcements_zeroizing() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+
+        let announcements: Vec<Announcement<Curve>> = [true, false, true]
+            .into_iter()
+            .map(|ours| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if ours {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+                } else {
+                    generate_announcement(other_public_key, other_public_key, ephemeral_private_key)
+                        .unwrap()
+                }
+            })
+            .collect();
+
+        let zeroizing = scan_announcements_zeroizing(&announcements, viewing_key, spending_key);
+        let expected = scan_announcements(&announcements, viewing_key, spending_key);
+        assert_eq!(zeroizing.len(), 2);
+        for ((index, stealth_private_key), result) in zeroizing.iter().zip(&expected) {
+            assert_eq!(*index, result.index);
+            assert_eq!(**stealth_private_key, result.stealth_private_key);
+        }
+        drop(zeroizing);
+    }
+
+    #[test]
+    fn test_recover_map() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+
+        let announcements: Vec<Announcement<Curve>> = (0..4)
+            .map(|i| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if i < 2 {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                } else {
+                    generate_announcement(other_public_key, other_public_key, ephemeral_private_key)
+                }
+                .unwrap()
+            })
+            .collect();
+
+        let keys = recover_map(&announcements, viewing_key, spending_key);
+        assert_eq!(keys.len(), 2);
+        let stealth_address = serialize_point(&announcements[1].stealth_commitment);
+        assert_eq!(
+            keys.get(&stealth_address),
+            recover(&announcements[1], viewing_key, spending_key).as_ref()
+        );
+        assert!(!keys.contains_key(&serialize_point(&announcements[2].stealth_commitment)));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_scan_announcements_span() {
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type Spans = Arc<Mutex<Vec<(&'static str, HashMap<&'static str, String>)>>>;
+
+        struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+        impl Visit for Fields<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.insert(field.name(), format!("{value:?}"));
+            }
+        }
+
+        /// Keeps the name and fields of every span, indexed by span id - 1.
+        struct SpanRecorder(Spans);
+
+        impl Subscriber for SpanRecorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.0.lock().unwrap();
+                let mut fields = HashMap::new();
+                span.record(&mut Fields(&mut fields));
+                spans.push((span.metadata().name(), fields));
+                Id::from_u64(spans.len() as u64)
+            }
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                let mut spans = self.0.lock().unwrap();
+                values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let announcements: Vec<Announcement<Curve>> = [true, false, true]
+            .into_iter()
+            .map(|ours| {
+                let (ephemeral_private_key, _) = Curve::random_keypair();
+                if ours {
+                    generate_announcement(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                } else {
+                    generate_announcement(other_public_key, other_public_key, ephemeral_private_key)
+                }
+                .unwrap()
+            })
+            .collect();
+
+        let spans = Spans::default();
+        tracing::subscriber::with_default(SpanRecorder(spans.clone()), || {
+            scan_announcements(&announcements, viewing_key, spending_key)
+        });
+
+        let spans = spans.lock().unwrap();
+        let scan: Vec<_> = spans
+            .iter()
+            .filter(|(name, _)| *name == "scan_announcements")
+            .collect();
+        assert_eq!(scan.len(), 1);
+        assert_eq!(scan[0].1["tag_checks"], "3");
+        assert_eq!(scan[0].1["matches"], "2");
+
+        let tag_checks: Vec<_> = spans
+            .iter()
+            .filter(|(name, _)| *name == "view_tag_check")
+            .map(|(_, fields)| fields["tag_matched"].as_str())
+            .collect();
+        assert_eq!(tag_checks, ["true", "false", "true"]);
+    }
+
+    #[test]
+    fn test_scan_report_mixed_tag_widths() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let announcements: Vec<_> = [1, 1, 1, 8]
+            .into_iter()
+            .map(|width| {
+                generate_announcement::<Curve>(
+                    viewing_public_key,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+                .with_view_tag_width(width)
+            })
+            .collect();
+
+        let report = scan_report(&announcements, viewing_key, spending_key);
+        assert_eq!(report.matched.len(), 4);
+        assert_eq!(report.tag_widths, [3, 0, 0, 0, 0, 0, 0, 1]);
+        let expected = 3.0 * anonymity_reduction(1) + anonymity_reduction(VIEW_TAG_BYTE_WIDTH);
+        assert_eq!(report.expected_tag_collisions(), expected);
+        assert_eq!(report.anonymity_reduction(), expected / 4.0);
+        assert!(report.anonymity_reduction() > 0.7 / 256.0);
+
+        let empty = scan_report::<Curve>(&[], viewing_key, spending_key);
+        assert_eq!(
+            empty.anonymity_reduction(),
+            anonymity_reduction(VIEW_TAG_BYTE_WIDTH)
+        );
+        assert_eq!(empty.expected_tag_collisions(), 0.0);
+    }
+
+    #[test]
+    fn test_scan_report() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let mut announcements: Vec<_> = (0..3)
+            .map(|_| {
+                generate_announcement::<Curve>(
+                    viewing_public_key,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+            })
+            .collect();
+        announcements.push(
+            generate_announcement::<Curve>(
+                other_public_key,
+                spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap(),
+        );
+        // a colliding tag: ours, but committing to someone else's stealth address
+        let mut collision = announcements[0].clone();
+        collision.stealth_commitment = other_public_key;
+        announcements.push(collision);
+
+        let report = scan_report(&announcements, viewing_key, spending_key);
+        assert_eq!(report.total, 5);
+        assert_eq!(report.tag_false_positives, 1);
+        assert_eq!(report.tag_widths, [0, 0, 0, 0, 0, 0, 0, 5]);
+        assert_eq!(
+            report.anonymity_reduction(),
+            anonymity_reduction(VIEW_TAG_BYTE_WIDTH)
+        );
+        assert!(report.expected_tag_collisions() < 1e-15);
+        assert_eq!(
+            report.matched,
+            scan_announcements(&announcements, viewing_key, spending_key)
+        );
+        assert_eq!(
+            report.matched.iter().map(|m| m.index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_owns_address() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, other_public_key) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            Curve::random_keypair().0,
+        )
+        .unwrap();
+
+        assert!(owns_address::<Curve>(
+            announcement.stealth_commitment,
+            announcement.ephemeral_public_key,
+            viewing_key,
+            spending_public_key
+        ));
+        assert!(!owns_address::<Curve>(
+            other_public_key,
+            announcement.ephemeral_public_key,
+            viewing_key,
+            spending_public_key
+        ));
+        assert!(!owns_address::<Curve>(
+            announcement.stealth_commitment,
+            announcement.ephemeral_public_key,
+            other_key,
+            spending_public_key
+        ));
+        assert!(!owns_address::<Curve>(
+            announcement.stealth_commitment,
+            announcement.ephemeral_public_key,
+            viewing_key,
+            other_public_key
+        ));
+    }
+
+    #[test]
+    fn test_scan_multi_recipient() {
+        let alice = (Curve::random_keypair(), Curve::random_keypair());
+        let bob = (Curve::random_keypair(), Curve::random_keypair());
+        let (_, stranger) = Curve::random_keypair();
+        let announce = |viewing_public_key, spending_public_key| {
+            generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap()
+        };
+        let announcements = [
+            announce(bob.0 .1, bob.1 .1),
+            announce(alice.0 .1, alice.1 .1),
+            announce(stranger, stranger),
+            announce(bob.0 .1, bob.1 .1),
+        ];
+        let recipients = [(alice.0 .0, alice.1 .0), (bob.0 .0, bob.1 .0)];
+
+        let matches = scan_multi_recipient(&announcements, &recipients);
+        assert_eq!(
+            matches
+                .iter()
+                .map(|(index, recipient, _)| (*index, *recipient))
+                .collect::<Vec<_>>(),
+            [(0, 1), (1, 0), (3, 1)]
+        );
+        for (index, recipient, stealth_private_key) in matches {
+            let (viewing_key, spending_key) = recipients[recipient];
+            assert_eq!(
+                recover(&announcements[index], viewing_key, spending_key),
+                Some(stealth_private_key)
+            );
+        }
+        assert!(scan_multi_recipient(&announcements, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_scan_sorted_ignores_input_order() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let mut announcements: Vec<_> = (0..8)
+            .map(|i| {
+                let viewing_public_key = if i % 3 == 0 {
+                    other_public_key
+                } else {
+                    viewing_public_key
+                };
+                generate_announcement::<Curve>(
+                    viewing_public_key,
+                    spending_public_key,
+                    Curve::random_keypair().0,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let without_index = |results: Vec<ScanResult<Curve>>| {
+            results
+                .into_iter()
+                .map(|result| {
+                    (
+                        result.stealth_private_key,
+                        result.stealth_address,
+                        result.ephemeral_public_key,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let sorted = scan_sorted(&announcements, viewing_key, spending_key);
+        assert_eq!(sorted.len(), 5);
+        assert!(sorted.windows(2).all(|pair| {
+            serialize_point(&pair[0].ephemeral_public_key)
+                <= serialize_point(&pair[1].ephemeral_public_key)
+        }));
+        for result in &sorted {
+            assert_eq!(
+                announcements[result.index].ephemeral_public_key,
+                result.ephemeral_public_key
+            );
+        }
+
+        let mut rng = StdRng::seed_from_u64(5564);
+        let expected = without_index(sorted);
+        for _ in 0..4 {
+            announcements.shuffle(&mut rng);
+            assert_eq!(
+                without_index(scan_sorted(&announcements, viewing_key, spending_key)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimate_scan_duration_scales_linearly() {
+        let estimate = estimate_scan_duration::<Curve>(1000);
+        assert!(estimate > Duration::ZERO);
+        assert_eq!(estimate_scan_duration::<Curve>(0), Duration::ZERO);
+        // calibrated once, so repeated calls agree
+        assert_eq!(estimate_scan_duration::<Curve>(1000), estimate);
+        assert_eq!(estimate_scan_duration::<Curve>(4000), estimate * 4);
+    }
+
+    #[test]
+    fn test_recover_with_tag_width_rejects_degenerate_shared_point() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, _) = Curve::random_keypair();
+        let identity = <Curve as StealthAddressOnCurve>::Projective::zero();
+        let announcement = Announcement::<Curve>::new(spending_public_key, identity, 0);
+        assert_eq!(
+            recover_with_tag_width(
+                &announcement,
+                viewing_key,
+                spending_key,
+                VIEW_TAG_BYTE_WIDTH
+            ),
+            Err(StealthError::DegenerateSharedPoint)
+        );
+        let announcement = Announcement::<Curve>::new(spending_public_key, spending_public_key, 0);
+        assert_eq!(
+            recover_with_tag_width(
+                &announcement,
+                <Curve as StealthAddressOnCurve>::Fr::zero(),
+                spending_key,
+                VIEW_TAG_BYTE_WIDTH
+            ),
+            Err(StealthError::DegenerateSharedPoint)
+        );
+    }
+
+    /// An announcement with the identity as ephemeral public key, which would match if the
+    /// identity were hashed like any shared point.
+    fn degenerate_announcement(
+        spending_public_key: <Curve as StealthAddressOnCurve>::Projective,
+    ) -> Announcement<Curve> {
+        let identity = <Curve as StealthAddressOnCurve>::Projective::zero();
+        let shared_secret = Curve::hash_to_fr(&shared_secret_hash_preimage(&identity));
+        Announcement::new(
+            spending_public_key + Curve::derive_public_key(&shared_secret),
+            identity,
+            view_tag_from_scalar(&shared_secret),
+        )
+    }
+
+    #[test]
+    fn test_owns_address_rejects_degenerate_shared_point() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, _) = Curve::random_keypair();
+        let announcement = degenerate_announcement(spending_public_key);
+        assert!(!owns_address::<Curve>(
+            announcement.stealth_commitment,
+            announcement.ephemeral_public_key,
+            viewing_key,
+            spending_public_key
+        ));
+    }
+
+    #[test]
+    fn test_diagnose_match_rejects_degenerate_shared_point() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, _) = Curve::random_keypair();
+        assert_eq!(
+            diagnose_match(
+                &degenerate_announcement(spending_public_key),
+                viewing_key,
+                spending_key
+            ),
+            MatchDiagnosis::DegenerateSharedPoint
+        );
+    }
+
+    #[test]
+    fn test_recovery_rejects_degenerate_shared_point() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, _) = Curve::random_keypair();
+        let identity = <Curve as StealthAddressOnCurve>::Projective::zero();
+        let announcement = degenerate_announcement(spending_public_key);
+
+        assert_eq!(recover(&announcement, viewing_key, spending_key), None);
+        let announcements = [announcement.clone(), announcement.clone()];
+        assert!(scan_announcements(&announcements, viewing_key, spending_key).is_empty());
+        scan_in_batches(&announcements, viewing_key, spending_key, 1, |batch| {
+            assert!(batch.is_empty())
+        });
+        let report = scan_report(&announcements, viewing_key, spending_key);
+        assert!(report.matched.is_empty());
+        assert_eq!(report.tag_false_positives, 0);
+        assert_eq!(
+            Curve::generate_stealth_private_key(
+                identity,
+                viewing_key,
+                spending_key,
+                announcement.view_tag
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_recover_with_tag_width() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            Curve::random_keypair().0,
+        )
+        .unwrap()
+        .with_view_tag_width(2);
+        assert!(announcement.view_tag <= 0xffff);
+
+        let stealth_private_key =
+            recover_with_tag_width(&announcement, viewing_key, spending_key, 2).unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            announcement.stealth_commitment
+        );
+        assert_eq!(
+            recover_with_tag_width(&announcement, viewing_key, spending_key, 1),
+            Err(StealthError::ViewTagWidthMismatch {
+                announced: 2,
+                configured: 1
+            })
+        );
+        assert_eq!(
+            recover_with_tag_width(&announcement, other_key, spending_key, 2),
+            Err(StealthError::NotRecipient)
+        );
+    }
+
+    #[test]
+    fn test_view_tag_width_survives_encoding() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            Curve::random_keypair().0,
+        )
+        .unwrap()
+        .with_view_tag_width(2);
+        let decoded = Announcement::<Curve>::from_bytes(&announcement.to_bytes()).unwrap();
+
+        assert_eq!(
+            recover_with_tag_width(&decoded, viewing_key, spending_key, VIEW_TAG_BYTE_WIDTH),
+            Err(StealthError::ViewTagWidthMismatch {
+                announced: 2,
+                configured: VIEW_TAG_BYTE_WIDTH
+            })
+        );
+        // recover and the scans compare only the announced bytes of the tag
+        let stealth_private_key = recover(&decoded, viewing_key, spending_key).unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            announcement.stealth_commitment
+        );
+        let matches = scan_announcements(std::slice::from_ref(&decoded), viewing_key, spending_key);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            scan_report(std::slice::from_ref(&decoded), viewing_key, spending_key).matched,
+            matches
+        );
+
+        let mut invalid = decoded;
+        invalid.view_tag_width = 0;
+        assert_eq!(recover(&invalid, viewing_key, spending_key), None);
+    }
+
+    #[test]
+    fn test_scan_with_reorg() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let announce = || {
+            generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap()
+        };
+        let announcements = [
+            announce().with_block_number(99),
+            announce().with_block_number(100),
+            announce(),
+            announce().with_block_number(101),
+            announce().with_block_number(12),
+        ];
+
+        let indices = |results: Vec<ScanResult<Curve>>| {
+            results
+                .iter()
+                .map(|result| result.index)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            indices(scan_with_reorg(
+                &announcements,
+                viewing_key,
+                spending_key,
+                100
+            )),
+            [0, 2, 4]
+        );
+        assert_eq!(
+            indices(scan_with_reorg(
+                &announcements,
+                viewing_key,
+                spending_key,
+                u64::MAX
+            )),
+            [0, 1, 2, 3, 4]
+        );
+    }
+}
@@ -0,0 +1,847 @@
+use crate::error::StealthError;
+use crate::serialization::serialize_point;
+use crate::stealth_commitments::{
+    constant_time_add, shared_secret_hash_preimage, validate_ephemeral_private_key,
+    StealthAddressOnCurve,
+};
+use crate::symmetric::SymmetricKdf;
+use crate::view_tag::{evm_view_tag, view_tag_from_scalar};
+use ark_ff::PrimeField;
+use num_traits::Zero;
+use std::marker::PhantomData;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Maps arbitrary bytes to a scalar field element.
+pub trait HashToField<F> {
+    fn hash_to_field(&self, input: &[u8]) -> F;
+}
+
+/// Domain of the shared secret to scalar step in [`StealthScheme::domain_separated_v1`], and of
+/// [`DomainSeparated::shared_secret`].
+pub const SHARED_SECRET_DOMAIN: &[u8] = b"ERC5564-SHARED";
+
+/// Domain of the commitment hash behind [`ViewTagSource::Commitment`] in
+/// [`StealthScheme::domain_separated_v1`], and of [`DomainSeparated::commitment`].
+pub const COMMITMENT_DOMAIN: &[u8] = b"ERC5564-COMMIT";
+
+/// Domain that empty input to [`StealthAddressOnCurve::hash_to_fr`] is hashed under, so the
+/// empty string maps to a constant the crate defines rather than whatever the underlying hash
+/// makes of no input.
+pub const EMPTY_INPUT_DOMAIN: &[u8] = b"ERC5564-EMPTY";
+
+/// `len(domain) || domain || len(input) || input`, with little-endian `u64` lengths.
+fn domain_preimage(domain: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut preimage = Vec::with_capacity(16 + domain.len() + input.len());
+    preimage.extend((domain.len() as u64).to_le_bytes());
+    preimage.extend(domain);
+    preimage.extend((input.len() as u64).to_le_bytes());
+    preimage.extend(input);
+    preimage
+}
+
+/// Domain separated [`StealthAddressOnCurve::hash_to_fr`]. Both the domain and the input are
+/// prefixed with their little-endian `u64` length, so no two `(domain, input)` pairs share a
+/// preimage.
+pub fn hash_to_fr_with_domain<C: StealthAddressOnCurve>(domain: &[u8], input: &[u8]) -> C::Fr {
+    C::hash_to_fr(&domain_preimage(domain, input))
+}
+
+/// A hasher whose inputs are framed with a domain as in [`hash_to_fr_with_domain`], so that the
+/// same hash used in two contexts can never alias.
+///
+/// [`StealthScheme::domain_separated_v1`] frames its own hashes this way; the wrapper is for
+/// integrators hashing in the same domains outside the scheme, e.g. a circuit input derived from
+/// a commitment.
+#[derive(Debug, Clone, Copy)]
+pub struct DomainSeparated<H> {
+    domain: &'static [u8],
+    hasher: H,
+}
+
+impl<H> DomainSeparated<H> {
+    pub fn new(domain: &'static [u8], hasher: H) -> Self {
+        DomainSeparated { domain, hasher }
+    }
+
+    /// `hasher` under [`SHARED_SECRET_DOMAIN`].
+    pub fn shared_secret(hasher: H) -> Self {
+        DomainSeparated::new(SHARED_SECRET_DOMAIN, hasher)
+    }
+
+    /// `hasher` under [`COMMITMENT_DOMAIN`].
+    pub fn commitment(hasher: H) -> Self {
+        DomainSeparated::new(COMMITMENT_DOMAIN, hasher)
+    }
+}
+
+impl<F, H: HashToField<F>> HashToField<F> for DomainSeparated<H> {
+    fn hash_to_field(&self, input: &[u8]) -> F {
+        self.hasher
+            .hash_to_field(&domain_preimage(self.domain, input))
+    }
+}
+
+/// Uses the curve's own [`StealthAddressOnCurve::hash_to_fr`], i.e. rln's `hash_to_field` and
+/// poseidon on bn254, and keccak on the bls curves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CurveHash<C>(PhantomData<C>);
+
+impl<C: StealthAddressOnCurve> HashToField<C::Fr> for CurveHash<C> {
+    fn hash_to_field(&self, input: &[u8]) -> C::Fr {
+        C::hash_to_fr(input)
+    }
+}
+
+/// Keccak-256 of the input, reduced into the field. As a tag hasher its input is the same
+/// display string preimage as the shared secret's; a tag contracts can reproduce comes from
+/// [`ViewTagSource::SharedPointKeccak`] instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeccakHash;
+
+impl<F: PrimeField> HashToField<F> for KeccakHash {
+    fn hash_to_field(&self, input: &[u8]) -> F {
+        let mut hash = [0; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(input);
+        hasher.finalize(&mut hash);
+        F::from_le_bytes_mod_order(hash.as_slice())
+    }
+}
+
+/// How the shared point is turned into hash input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SharedSecretEncoding {
+    /// The point's display string, hashed with the scheme's hasher. This is what the
+    /// [`StealthAddressOnCurve`] methods use.
+    #[default]
+    Bytes,
+    /// The point's affine coordinates, hashed with
+    /// [`StealthAddressOnCurve::hash_coordinates_to_fr`], i.e. `poseidon_hash([x, y])` on bn254.
+    /// The scheme's hasher is not used in this mode.
+    Coordinates,
+}
+
+/// What the view tag is derived from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ViewTagSource {
+    /// The hashed shared secret, or the tag hasher's hash of the shared point if one is set.
+    #[default]
+    SharedSecret,
+    /// The first byte of `keccak256` of the compressed stealth commitment, for EVM verifiers that
+    /// can hash the stealth address but not the shared secret. [`StealthScheme::domain_separated_v1`]
+    /// hashes the commitment under [`COMMITMENT_DOMAIN`]. The tag hasher is not used in
+    /// this mode, and scanning must derive each candidate commitment before it can check the
+    /// tag, so the tag no longer saves the scalar multiplication on a mismatch.
+    Commitment,
+    /// [`evm_view_tag`] of the shared point, the low 64 bits of `keccak256(X || Y)` as a
+    /// `uint256`, for contracts that check tags against a shared point they are given. The tag
+    /// hasher is not used in this mode.
+    SharedPointKeccak,
+}
+
+/// The stealth commitment scheme with a pluggable shared secret hash, for integrators that must
+/// match a different on-chain hashing contract. With the default [`CurveHash`] it behaves exactly
+/// like the [`StealthAddressOnCurve`] methods.
+///
+/// The view tag is taken from the commitment hash unless a separate tag hasher is configured with
+/// [`StealthScheme::with_tag_hasher`].
+#[derive(Debug, Clone)]
+pub struct StealthScheme<
+    C: StealthAddressOnCurve,
+    H: HashToField<C::Fr> = CurveHash<C>,
+    T: HashToField<C::Fr> = H,
+> {
+    hasher: H,
+    tag_hasher: Option<T>,
+    encoding: SharedSecretEncoding,
+    tag_source: ViewTagSource,
+    symmetric_kdf: SymmetricKdf,
+    domain_separated: bool,
+    _curve: PhantomData<C>,
+}
+
+impl<C: StealthAddressOnCurve> Default for StealthScheme<C> {
+    fn default() -> Self {
+        StealthScheme::with_hasher(CurveHash(PhantomData))
+    }
+}
+
+impl<C: StealthAddressOnCurve> StealthScheme<C> {
+    /// Version 1 of the domain separated scheme. The shared secret is hashed under
+    /// [`SHARED_SECRET_DOMAIN`], with either [`SharedSecretEncoding`], and the commitment behind
+    /// [`ViewTagSource::Commitment`] under [`COMMITMENT_DOMAIN`], both framed as in
+    /// [`hash_to_fr_with_domain`], so the two contexts can never alias.
+    ///
+    /// Its commitments and tags differ from those of the default scheme and the
+    /// [`StealthAddressOnCurve`] methods, which keep hashing without a domain for the
+    /// announcements already published, so sender and recipient must both use it. What v1 hashes
+    /// never changes; a change of domains or framing gets a new version.
+    /// [`ViewTagSource::SharedPointKeccak`] stays undomained, as contracts compute it.
+    pub fn domain_separated_v1() -> Self {
+        StealthScheme {
+            domain_separated: true,
+            ..StealthScheme::default()
+        }
+    }
+}
+
+impl<C: StealthAddressOnCurve, H: HashToField<C::Fr>> StealthScheme<C, H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        StealthScheme {
+            hasher,
+            tag_hasher: None,
+            encoding: SharedSecretEncoding::Bytes,
+            tag_source: ViewTagSource::SharedSecret,
+            symmetric_kdf: SymmetricKdf::HkdfSha256,
+            domain_separated: false,
+            _curve: PhantomData,
+        }
+    }
+}
+
+impl<C: StealthAddressOnCurve, H: HashToField<C::Fr>, T: HashToField<C::Fr>>
+    StealthScheme<C, H, T>
+{
+    /// Derives view tags by hashing the shared secret with `tag_hasher`, independently of the
+    /// hash used for the commitment. Sender and recipient must agree on both.
+    pub fn with_tag_hasher<U: HashToField<C::Fr>>(self, tag_hasher: U) -> StealthScheme<C, H, U> {
+        StealthScheme {
+            hasher: self.hasher,
+            tag_hasher: Some(tag_hasher),
+            encoding: self.encoding,
+            tag_source: self.tag_source,
+            symmetric_kdf: self.symmetric_kdf,
+            domain_separated: self.domain_separated,
+            _curve: PhantomData,
+        }
+    }
+
+    /// Selects how the shared point is hashed. Sender and recipient must use the same encoding.
+    pub fn with_encoding(mut self, encoding: SharedSecretEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Selects what the view tag is derived from. Sender and recipient must use the same source.
+    pub fn with_view_tag_source(mut self, tag_source: ViewTagSource) -> Self {
+        self.tag_source = tag_source;
+        self
+    }
+
+    /// Selects the KDF for [`StealthScheme::symmetric_shared_key`]. Sender and recipient must use
+    /// the same KDF.
+    pub fn with_symmetric_kdf(mut self, symmetric_kdf: SymmetricKdf) -> Self {
+        self.symmetric_kdf = symmetric_kdf;
+        self
+    }
+
+    /// Like [`crate::symmetric::symmetric_shared_key`], with the scheme's [`SymmetricKdf`].
+    pub fn symmetric_shared_key(
+        &self,
+        ephemeral_private_key: C::Fr,
+        viewing_public_key: C::Projective,
+    ) -> [u8; 32] {
+        self.symmetric_kdf.derive_key::<C>(&C::compute_shared_point(
+            ephemeral_private_key,
+            viewing_public_key,
+        ))
+    }
+
+    /// The recipient's side of [`StealthScheme::symmetric_shared_key`].
+    pub fn symmetric_shared_key_recipient(
+        &self,
+        viewing_key: C::Fr,
+        ephemeral_public_key: C::Projective,
+    ) -> [u8; 32] {
+        self.symmetric_kdf
+            .derive_key::<C>(&C::compute_shared_point(viewing_key, ephemeral_public_key))
+    }
+
+    fn hash_shared_point(&self, shared_point: &C::Projective, preimage: &[u8]) -> C::Fr {
+        match (self.encoding, self.domain_separated) {
+            (SharedSecretEncoding::Bytes, false) => self.hasher.hash_to_field(preimage),
+            (SharedSecretEncoding::Bytes, true) => self
+                .hasher
+                .hash_to_field(&domain_preimage(SHARED_SECRET_DOMAIN, preimage)),
+            (SharedSecretEncoding::Coordinates, false) => C::hash_coordinates_to_fr(shared_point),
+            (SharedSecretEncoding::Coordinates, true) => {
+                C::hash_coordinates_to_fr_with_domain(SHARED_SECRET_DOMAIN, shared_point)
+            }
+        }
+    }
+
+    fn view_tag(
+        &self,
+        shared_point: &C::Projective,
+        preimage: &[u8],
+        q_hashed: C::Fr,
+        commitment: &C::Projective,
+    ) -> u64 {
+        match (self.tag_source, &self.tag_hasher) {
+            (ViewTagSource::SharedPointKeccak, _) => evm_view_tag(shared_point),
+            (ViewTagSource::Commitment, _) => {
+                let mut hash = [0; 32];
+                let mut hasher = Keccak::v256();
+                if self.domain_separated {
+                    hasher.update(&domain_preimage(
+                        COMMITMENT_DOMAIN,
+                        &serialize_point(commitment),
+                    ));
+                } else {
+                    hasher.update(&serialize_point(commitment));
+                }
+                hasher.finalize(&mut hash);
+                hash[0].into()
+            }
+            (ViewTagSource::SharedSecret, Some(tag_hasher)) => {
+                view_tag_from_scalar(&tag_hasher.hash_to_field(preimage))
+            }
+            (ViewTagSource::SharedSecret, None) => view_tag_from_scalar(&q_hashed),
+        }
+    }
+
+    pub fn generate_stealth_commitment(
+        &self,
+        viewing_public_key: C::Projective,
+        spending_public_key: C::Projective,
+        ephemeral_private_key: C::Fr,
+    ) -> Result<(C::Projective, u64), StealthError> {
+        validate_ephemeral_private_key(&ephemeral_private_key)?;
+        let q = C::compute_shared_point(ephemeral_private_key, viewing_public_key);
+        if q.is_zero() {
+            return Err(StealthError::IdentityPoint);
+        }
+        let inputs = shared_secret_hash_preimage(&q);
+        let q_hashed = self.hash_shared_point(&q, &inputs);
+
+        let stealth_commitment = C::derive_public_key(&q_hashed) + spending_public_key;
+        let view_tag = self.view_tag(&q, &inputs, q_hashed, &stealth_commitment);
+        Ok((stealth_commitment, view_tag))
+    }
+
+    pub fn generate_stealth_private_key(
+        &self,
+        ephemeral_public_key: C::Projective,
+        viewing_key: C::Fr,
+        spending_key: C::Fr,
+        expected_view_tag: u64,
+    ) -> Option<C::Fr> {
+        let q_receiver = C::compute_shared_point(viewing_key, ephemeral_public_key);
+        // an identity shared point can only come from malformed data
+        if q_receiver.is_zero() {
+            return None;
+        }
+        let inputs_receiver = shared_secret_hash_preimage(&q_receiver);
+        let q_receiver_hashed = self.hash_shared_point(&q_receiver, &inputs_receiver);
+
+        let stealth_private_key = constant_time_add(spending_key, q_receiver_hashed);
+        let candidate_commitment = match self.tag_source {
+            ViewTagSource::SharedSecret | ViewTagSource::SharedPointKeccak => C::Projective::zero(),
+            ViewTagSource::Commitment => C::derive_public_key(&stealth_private_key),
+        };
+        if self.view_tag(
+            &q_receiver,
+            &inputs_receiver,
+            q_receiver_hashed,
+            &candidate_commitment,
+        ) == expected_view_tag
+        {
+            Some(stealth_private_key)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::serialization::{deserialize_fr, serialize_fr};
+
+    type Curve = crate::TestCurve;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    struct MockHash;
+
+    impl HashToField<Fr> for MockHash {
+        fn hash_to_field(&self, input: &[u8]) -> Fr {
+            Fr::from_le_bytes_mod_order(input) + Fr::from(input.len() as u64)
+        }
+    }
+
+    #[test]
+    fn test_default_scheme_matches_curve() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let scheme = StealthScheme::<Curve>::default();
+
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        assert_eq!(
+            (stealth_commitment, view_tag),
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            scheme.generate_stealth_private_key(
+                ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                view_tag
+            ),
+            Curve::generate_stealth_private_key(
+                ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                view_tag
+            )
+        );
+    }
+
+    #[test]
+    fn test_scheme_rejects_degenerate_shared_point() {
+        let (spending_key, _) = Curve::random_keypair();
+        let (viewing_key, _) = Curve::random_keypair();
+        let identity = <Curve as StealthAddressOnCurve>::Projective::zero();
+        // the tag the identity would produce if it were hashed like any shared point
+        let view_tag =
+            view_tag_from_scalar(&Curve::hash_to_fr(&shared_secret_hash_preimage(&identity)));
+        for scheme in [
+            StealthScheme::<Curve>::default(),
+            StealthScheme::<Curve>::default().with_view_tag_source(ViewTagSource::Commitment),
+        ] {
+            assert_eq!(
+                scheme.generate_stealth_private_key(identity, viewing_key, spending_key, view_tag),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_hasher_round_trip() {
+        assert_eq!(
+            MockHash.hash_to_field(b"input"),
+            MockHash.hash_to_field(b"input")
+        );
+
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let scheme = StealthScheme::<Curve, _>::with_hasher(MockHash);
+
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        assert_ne!(
+            stealth_commitment,
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+            .0
+        );
+
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .expect("view tags did not match");
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+    }
+
+    #[test]
+    fn test_separate_tag_hasher_round_trip() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let scheme = StealthScheme::<Curve>::default().with_tag_hasher(KeccakHash);
+
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        // the commitment still comes from the curve hash, only the tag changes
+        assert_eq!(
+            stealth_commitment,
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+            .0
+        );
+        let shared_point = Curve::compute_shared_point(ephemeral_private_key, viewing_public_key);
+        assert_eq!(
+            view_tag,
+            view_tag_from_scalar(&HashToField::<Fr>::hash_to_field(
+                &KeccakHash,
+                &shared_secret_hash_preimage(&shared_point)
+            ))
+        );
+
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .expect("view tags did not match");
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+
+        let mismatched = StealthScheme::<Curve>::default().with_tag_hasher(MockHash);
+        assert_eq!(
+            mismatched.generate_stealth_private_key(
+                ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                view_tag
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shared_point_keccak_view_tag_round_trip() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let scheme = StealthScheme::<Curve>::default()
+            .with_view_tag_source(ViewTagSource::SharedPointKeccak)
+            .with_tag_hasher(MockHash);
+
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        let shared_point = Curve::compute_shared_point(ephemeral_private_key, viewing_public_key);
+        assert_eq!(view_tag, evm_view_tag(&shared_point));
+
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .expect("view tags did not match");
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+        assert_eq!(
+            StealthScheme::<Curve>::default().generate_stealth_private_key(
+                ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                view_tag
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shared_secret_encodings() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+
+        let mut commitments = Vec::new();
+        for encoding in [
+            SharedSecretEncoding::Bytes,
+            SharedSecretEncoding::Coordinates,
+        ] {
+            let scheme = StealthScheme::<Curve>::default().with_encoding(encoding);
+            let (stealth_commitment, view_tag) = scheme
+                .generate_stealth_commitment(
+                    viewing_public_key,
+                    spending_public_key,
+                    ephemeral_private_key,
+                )
+                .unwrap();
+            let stealth_private_key = scheme
+                .generate_stealth_private_key(
+                    ephemeral_public_key,
+                    viewing_key,
+                    spending_key,
+                    view_tag,
+                )
+                .expect("view tags did not match");
+            assert_eq!(
+                Curve::derive_public_key(&stealth_private_key),
+                stealth_commitment
+            );
+            commitments.push(stealth_commitment);
+        }
+        assert_ne!(commitments[0], commitments[1]);
+    }
+
+    #[test]
+    fn test_hash_to_fr_with_domain_is_unambiguous() {
+        let hash = hash_to_fr_with_domain::<Curve>;
+        assert_ne!(hash(b"ab", b"c"), hash(b"a", b"bc"));
+        assert_ne!(hash(b"", b"abc"), hash(b"abc", b""));
+        assert_ne!(hash(b"a", b""), Curve::hash_to_fr(b"a"));
+        assert_eq!(hash(b"ab", b"c"), hash(b"ab", b"c"));
+    }
+
+    #[test]
+    fn test_shared_secret_and_commitment_domains_differ() {
+        let hasher = CurveHash::<Curve>(PhantomData);
+        let shared = DomainSeparated::shared_secret(hasher);
+        let commitment = DomainSeparated::commitment(hasher);
+        let input = b"the same input";
+        let shared_hash: Fr = shared.hash_to_field(input);
+        let commitment_hash: Fr = commitment.hash_to_field(input);
+        assert_ne!(shared_hash, commitment_hash);
+        assert_ne!(shared_hash, Curve::hash_to_fr(input));
+        assert_eq!(
+            shared_hash,
+            hash_to_fr_with_domain::<Curve>(SHARED_SECRET_DOMAIN, input)
+        );
+
+        let scheme = StealthScheme::<Curve, _>::with_hasher(shared);
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        assert_ne!(
+            stealth_commitment,
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+            .0
+        );
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .expect("view tags did not match");
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+    }
+
+    #[test]
+    fn test_domain_separated_v1() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let shared_point = Curve::compute_shared_point(ephemeral_private_key, viewing_public_key);
+        let preimage = shared_secret_hash_preimage(&shared_point);
+
+        let default = StealthScheme::<Curve>::default();
+        let separated = StealthScheme::<Curve>::domain_separated_v1();
+        for encoding in [
+            SharedSecretEncoding::Bytes,
+            SharedSecretEncoding::Coordinates,
+        ] {
+            let expected = match encoding {
+                SharedSecretEncoding::Bytes => {
+                    hash_to_fr_with_domain::<Curve>(SHARED_SECRET_DOMAIN, &preimage)
+                }
+                SharedSecretEncoding::Coordinates => {
+                    Curve::hash_coordinates_to_fr_with_domain(SHARED_SECRET_DOMAIN, &shared_point)
+                }
+            };
+            let scheme = separated.clone().with_encoding(encoding);
+            let (stealth_commitment, view_tag) = scheme
+                .generate_stealth_commitment(
+                    viewing_public_key,
+                    spending_public_key,
+                    ephemeral_private_key,
+                )
+                .unwrap();
+            assert_eq!(
+                (stealth_commitment, view_tag),
+                Curve::commitment_from_hashed_secret(expected, spending_public_key)
+            );
+            assert_ne!(
+                (stealth_commitment, view_tag),
+                default
+                    .clone()
+                    .with_encoding(encoding)
+                    .generate_stealth_commitment(
+                        viewing_public_key,
+                        spending_public_key,
+                        ephemeral_private_key,
+                    )
+                    .unwrap()
+            );
+            assert_eq!(
+                scheme.generate_stealth_private_key(
+                    ephemeral_public_key,
+                    viewing_key,
+                    spending_key,
+                    view_tag
+                ),
+                Some(constant_time_add(spending_key, expected))
+            );
+        }
+
+        // the commitment tag is framed under the commitment domain
+        let scheme = separated.with_view_tag_source(ViewTagSource::Commitment);
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        let mut hash = [0; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(&domain_preimage(
+            COMMITMENT_DOMAIN,
+            &serialize_point(&stealth_commitment),
+        ));
+        hasher.finalize(&mut hash);
+        assert_eq!(view_tag, u64::from(hash[0]));
+        assert!(scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .is_some());
+    }
+
+    #[test]
+    fn test_commitment_view_tag_round_trip() {
+        let scheme =
+            StealthScheme::<Curve>::default().with_view_tag_source(ViewTagSource::Commitment);
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+
+        let (stealth_commitment, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        let mut hash = [0; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(&serialize_point(&stealth_commitment));
+        hasher.finalize(&mut hash);
+        assert_eq!(view_tag, u64::from(hash[0]));
+        // the commitment itself does not depend on where the tag comes from
+        assert_eq!(
+            stealth_commitment,
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+            .0
+        );
+
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .expect("view tags did not match");
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+        let misses = (0..8)
+            .filter(|_| {
+                scheme
+                    .generate_stealth_private_key(
+                        ephemeral_public_key,
+                        other_key,
+                        Curve::random_keypair().0,
+                        view_tag,
+                    )
+                    .is_none()
+            })
+            .count();
+        assert!(misses > 0);
+    }
+
+    #[test]
+    fn test_symmetric_kdfs_agree_within_and_differ_across() {
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let keys: Vec<_> = [SymmetricKdf::HkdfSha256, SymmetricKdf::Poseidon]
+            .into_iter()
+            .map(|kdf| {
+                let scheme = StealthScheme::<Curve>::default().with_symmetric_kdf(kdf);
+                let key = scheme.symmetric_shared_key(ephemeral_private_key, viewing_public_key);
+                assert_eq!(
+                    scheme.symmetric_shared_key_recipient(viewing_key, ephemeral_public_key),
+                    key
+                );
+                key
+            })
+            .collect();
+        assert_ne!(keys[0], keys[1]);
+        assert_eq!(
+            StealthScheme::<Curve>::default()
+                .symmetric_shared_key(ephemeral_private_key, viewing_public_key),
+            keys[0]
+        );
+        // the free functions use the same default KDF
+        assert_eq!(
+            keys[0],
+            crate::symmetric::symmetric_shared_key::<Curve>(
+                ephemeral_private_key,
+                viewing_public_key
+            )
+        );
+    }
+
+    #[test]
+    fn test_poseidon_symmetric_key_is_not_the_shared_secret() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let scheme = StealthScheme::<Curve>::default()
+            .with_encoding(SharedSecretEncoding::Coordinates)
+            .with_symmetric_kdf(SymmetricKdf::Poseidon);
+
+        let shared_point = Curve::compute_shared_point(ephemeral_private_key, viewing_public_key);
+        let shared_secret = scheme.hash_shared_point(&shared_point, &[]);
+        let key = scheme.symmetric_shared_key(ephemeral_private_key, viewing_public_key);
+        assert_ne!(key.to_vec(), serialize_fr(&shared_secret));
+
+        // the memo key does not give away the stealth key either
+        let (_, view_tag) = scheme
+            .generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key,
+            )
+            .unwrap();
+        let stealth_private_key = scheme
+            .generate_stealth_private_key(ephemeral_public_key, viewing_key, spending_key, view_tag)
+            .unwrap();
+        assert_ne!(
+            stealth_private_key,
+            constant_time_add(spending_key, deserialize_fr(&key).unwrap_or_default())
+        );
+    }
+}
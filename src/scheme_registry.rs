@@ -0,0 +1,59 @@
+use crate::error::StealthError;
+
+// ERC-5564 only registers secp256k1 (scheme id 1), so the pairing curves use ids assigned by this crate.
+pub const BN254_SCHEME_ID: u8 = 2;
+pub const BLS12_381_SCHEME_ID: u8 = 3;
+pub const BLS12_377_SCHEME_ID: u8 = 4;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Bn254,
+    Bls12_381,
+    Bls12_377,
+}
+
+impl Scheme {
+    pub const fn id(self) -> u8 {
+        match self {
+            Scheme::Bn254 => BN254_SCHEME_ID,
+            Scheme::Bls12_381 => BLS12_381_SCHEME_ID,
+            Scheme::Bls12_377 => BLS12_377_SCHEME_ID,
+        }
+    }
+}
+
+/// Maps a scheme id to its curve, failing for ids whose curve is not compiled in.
+pub fn lookup(scheme_id: u8) -> Result<Scheme, StealthError> {
+    match scheme_id {
+        #[cfg(feature = "bn254")]
+        BN254_SCHEME_ID => Ok(Scheme::Bn254),
+        #[cfg(feature = "bls12_381")]
+        BLS12_381_SCHEME_ID => Ok(Scheme::Bls12_381),
+        #[cfg(feature = "bls12_377")]
+        BLS12_377_SCHEME_ID => Ok(Scheme::Bls12_377),
+        _ => Err(StealthError::UnsupportedScheme(scheme_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stealth_commitments::StealthAddressOnCurve;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup(Curve::SCHEME.id()), Ok(Curve::SCHEME));
+        assert_eq!(lookup(1), Err(StealthError::UnsupportedScheme(1)));
+        for scheme in [Scheme::Bn254, Scheme::Bls12_381, Scheme::Bls12_377] {
+            if scheme != Curve::SCHEME {
+                assert_eq!(
+                    lookup(scheme.id()),
+                    Err(StealthError::UnsupportedScheme(scheme.id()))
+                );
+            }
+        }
+    }
+}
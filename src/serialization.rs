@@ -0,0 +1,290 @@
+use crate::error::StealthError;
+use crate::stealth_commitments::StealthAddressOnCurve;
+use ark_ec::CurveGroup;
+use ark_ff::PrimeField;
+
+/// Size in bytes of a compressed point of `G`.
+pub fn public_key_size<G: CurveGroup>() -> usize {
+    G::zero().compressed_size()
+}
+
+/// Compressed encoding without the public key policy checks, for points that are not keys.
+pub(crate) fn serialize_point<G: CurveGroup>(point: &G) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(public_key_size::<G>());
+    point
+        .serialize_compressed(&mut buf)
+        .expect("serializing into a Vec cannot fail");
+    buf
+}
+
+/// Compressed encoding of a public key. The identity is never a valid public key and is
+/// rejected rather than encoded with arkworks' infinity flag.
+pub fn serialize_public_key<G: CurveGroup>(point: &G) -> Result<Vec<u8>, StealthError> {
+    if point.is_zero() {
+        return Err(StealthError::IdentityPoint);
+    }
+    Ok(serialize_point(point))
+}
+
+/// Decodes a compressed public key, checking that it is on the curve, in the prime order
+/// subgroup and not the identity.
+pub fn deserialize_public_key<G: CurveGroup>(bytes: &[u8]) -> Result<G, StealthError> {
+    let expected = public_key_size::<G>();
+    if bytes.len() != expected {
+        return Err(StealthError::InvalidLength {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    let point = G::deserialize_compressed(bytes)?;
+    if point.is_zero() {
+        return Err(StealthError::IdentityPoint);
+    }
+    Ok(point)
+}
+
+/// Like [`deserialize_public_key`], but also accepts the uncompressed encoding that some chains
+/// log, telling the two apart by length and decoding it with
+/// [`StealthAddressOnCurve::deserialize_public_key_uncompressed`], e.g. the EVM big-endian
+/// `X || Y` on bn254. Either form is checked to be on the curve and in the prime order subgroup.
+pub fn deserialize_public_key_any<C: StealthAddressOnCurve>(
+    bytes: &[u8],
+) -> Result<C::Projective, StealthError> {
+    if bytes.len() != 2 * public_key_size::<C::Projective>() {
+        return deserialize_public_key(bytes);
+    }
+    C::deserialize_public_key_uncompressed(bytes)
+}
+
+/// Like [`deserialize_public_key`], but skips the on-curve and subgroup checks.
+///
+/// Only for bytes the caller has already validated, e.g. an indexer re-reading its own store.
+/// Invalid input yields a point that breaks the protocol's guarantees.
+pub fn deserialize_public_key_unchecked<G: CurveGroup>(bytes: &[u8]) -> Result<G, StealthError> {
+    let expected = public_key_size::<G>();
+    if bytes.len() != expected {
+        return Err(StealthError::InvalidLength {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    let point = G::deserialize_compressed_unchecked(bytes)?;
+    if point.is_zero() {
+        return Err(StealthError::IdentityPoint);
+    }
+    Ok(point)
+}
+
+/// Checks that a point is a valid public key: not the identity, on the curve and in the prime
+/// order subgroup.
+pub fn validate_public_key<G: CurveGroup>(point: &G) -> Result<(), StealthError> {
+    if point.is_zero() {
+        return Err(StealthError::IdentityPoint);
+    }
+    let mut bytes = Vec::new();
+    point
+        .serialize_uncompressed(&mut bytes)
+        .expect("serializing into a Vec cannot fail");
+    // decoding with validation rejects points off the curve or outside the subgroup
+    if G::deserialize_uncompressed(bytes.as_slice())? != *point {
+        return Err(StealthError::InvalidEncoding);
+    }
+    Ok(())
+}
+
+/// Size in bytes of an encoded scalar of `F`.
+pub fn scalar_size<F: PrimeField>() -> usize {
+    F::zero().compressed_size()
+}
+
+pub fn serialize_fr<F: PrimeField>(scalar: &F) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(scalar_size::<F>());
+    scalar
+        .serialize_compressed(&mut buf)
+        .expect("serializing into a Vec cannot fail");
+    buf
+}
+
+/// Decodes a little-endian scalar, rejecting encodings that are not canonical (i.e. `>= modulus`):
+/// `modulus - 1` is the largest scalar accepted.
+pub fn deserialize_fr<F: PrimeField>(bytes: &[u8]) -> Result<F, StealthError> {
+    let expected = scalar_size::<F>();
+    if bytes.len() != expected {
+        return Err(StealthError::InvalidLength {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(F::deserialize_compressed(bytes)?)
+}
+
+/// Reduces little-endian bytes of any length modulo the field order, e.g. to use a hash from
+/// another system as a scalar. Unlike [`deserialize_fr`], which rejects non-canonical encodings,
+/// every input maps to a valid scalar. The result is close to uniform for uniform input at least
+/// 128 bits longer than the modulus, such as a 64 byte hash; a 32 byte hash is slightly biased.
+pub fn reduce_bytes_to_fr<F: PrimeField>(bytes: &[u8]) -> F {
+    F::from_le_bytes_mod_order(bytes)
+}
+
+/// Byte level [`StealthAddressOnCurve::compute_shared_point`], taking an encoded scalar and a
+/// compressed point and returning the compressed shared point.
+pub fn compute_shared_point_bytes<C: StealthAddressOnCurve>(
+    private_key: &[u8],
+    other_public_key: &[u8],
+) -> Result<Vec<u8>, StealthError> {
+    let private_key = deserialize_fr::<C::Fr>(private_key)?;
+    let other_public_key = deserialize_public_key::<C::Projective>(other_public_key)?;
+    Ok(serialize_point(&C::compute_shared_point(
+        private_key,
+        other_public_key,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::BigInteger;
+    use num_traits::Zero;
+
+    type Curve = crate::TestCurve;
+    type Projective = <Curve as StealthAddressOnCurve>::Projective;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    #[test]
+    fn test_public_key_round_trip() {
+        let (_, public_key) = Curve::random_keypair();
+        let bytes = serialize_public_key(&public_key).unwrap();
+        assert_eq!(bytes.len(), public_key_size::<Projective>());
+        assert_eq!(deserialize_public_key::<Projective>(&bytes), Ok(public_key));
+    }
+
+    #[test]
+    fn test_deserialize_public_key_any() {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "bn254")] {
+                let uncompressed = |point: &Projective| {
+                    crate::bn254_impl::serialize_public_key_uncompressed(point).to_vec()
+                };
+            } else {
+                let uncompressed = |point: &Projective| {
+                    let mut bytes = Vec::new();
+                    ark_serialize::CanonicalSerialize::serialize_uncompressed(point, &mut bytes)
+                        .unwrap();
+                    bytes
+                };
+            }
+        }
+        let (_, public_key) = Curve::random_keypair();
+        let compressed = serialize_public_key(&public_key).unwrap();
+        let uncompressed_key = uncompressed(&public_key);
+        assert_eq!(uncompressed_key.len(), 2 * compressed.len());
+
+        assert_eq!(
+            deserialize_public_key_any::<Curve>(&compressed),
+            Ok(public_key)
+        );
+        assert_eq!(
+            deserialize_public_key_any::<Curve>(&uncompressed_key),
+            Ok(public_key)
+        );
+
+        assert_eq!(
+            deserialize_public_key_any::<Curve>(&uncompressed_key[1..]),
+            Err(StealthError::InvalidLength {
+                expected: compressed.len(),
+                actual: uncompressed_key.len() - 1
+            })
+        );
+        let mut off_curve = uncompressed_key.clone();
+        off_curve[uncompressed_key.len() - 1] ^= 1;
+        assert!(deserialize_public_key_any::<Curve>(&off_curve).is_err());
+        assert_eq!(
+            deserialize_public_key_any::<Curve>(&uncompressed(&Projective::zero())),
+            Err(StealthError::IdentityPoint)
+        );
+    }
+
+    #[test]
+    fn test_serialize_identity_rejected() {
+        assert_eq!(
+            serialize_public_key(&Projective::zero()),
+            Err(StealthError::IdentityPoint)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_identity_rejected() {
+        let bytes = serialize_point(&Projective::zero());
+        assert_eq!(
+            deserialize_public_key::<Projective>(&bytes),
+            Err(StealthError::IdentityPoint)
+        );
+    }
+
+    #[test]
+    fn test_compute_shared_point_bytes() {
+        let (private_key, _) = Curve::random_keypair();
+        let (_, other_public_key) = Curve::random_keypair();
+        let private_key_bytes = serialize_fr(&private_key);
+        let other_public_key_bytes = serialize_public_key(&other_public_key).unwrap();
+
+        let shared =
+            compute_shared_point_bytes::<Curve>(&private_key_bytes, &other_public_key_bytes)
+                .unwrap();
+        assert_eq!(
+            shared,
+            serialize_point(&Curve::compute_shared_point(private_key, other_public_key))
+        );
+    }
+
+    #[test]
+    fn test_compute_shared_point_bytes_rejects_bad_lengths() {
+        let (private_key, other_public_key) = Curve::random_keypair();
+        let private_key_bytes = serialize_fr(&private_key);
+        let other_public_key_bytes = serialize_public_key(&other_public_key).unwrap();
+
+        let mut long_private_key = private_key_bytes.clone();
+        long_private_key.push(0);
+        for bad in [&private_key_bytes[1..], &long_private_key[..]] {
+            assert_eq!(
+                compute_shared_point_bytes::<Curve>(bad, &other_public_key_bytes),
+                Err(StealthError::InvalidLength {
+                    expected: scalar_size::<Fr>(),
+                    actual: bad.len(),
+                })
+            );
+        }
+
+        let mut long_public_key = other_public_key_bytes.clone();
+        long_public_key.push(0);
+        for bad in [&other_public_key_bytes[1..], &long_public_key[..], &[]] {
+            assert_eq!(
+                compute_shared_point_bytes::<Curve>(&private_key_bytes, bad),
+                Err(StealthError::InvalidLength {
+                    expected: public_key_size::<Projective>(),
+                    actual: bad.len(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_reduce_bytes_to_fr() {
+        let above_modulus = |offset: u64| {
+            let mut value = Fr::MODULUS;
+            value.add_with_carry(&<Fr as PrimeField>::BigInt::from(offset));
+            value.to_bytes_le()
+        };
+        let (one, two) = (above_modulus(1), above_modulus(2));
+        assert!(deserialize_fr::<Fr>(&one).is_err());
+        assert!(deserialize_fr::<Fr>(&two).is_err());
+        assert_eq!(reduce_bytes_to_fr::<Fr>(&one), Fr::from(1u64));
+        assert_eq!(reduce_bytes_to_fr::<Fr>(&two), Fr::from(2u64));
+
+        let (scalar, _) = Curve::random_keypair();
+        let mut wide = serialize_fr(&scalar);
+        wide.resize(2 * scalar_size::<Fr>(), 0);
+        assert_eq!(reduce_bytes_to_fr::<Fr>(&wide), scalar);
+        assert_eq!(reduce_bytes_to_fr::<Fr>(&[]), Fr::zero());
+    }
+}
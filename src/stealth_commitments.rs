@@ -1,5 +1,15 @@
+use crate::error::StealthError;
+use crate::scheme_registry::Scheme;
+use crate::serialization::validate_public_key;
+use crate::view_tag::view_tag_from_scalar;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+#[cfg(feature = "os_rng")]
 use ark_std::rand::rngs::OsRng;
+use ark_std::rand::Rng;
 use ark_std::UniformRand;
+use num_traits::{One, Zero};
 use std::fmt::Display;
 use std::ops::{Add, Mul};
 
@@ -8,30 +18,124 @@ pub trait AffineWrapper {
     fn new(x: Self::Fq, y: Self::Fq) -> Self;
 }
 
+/// Access to the low limb of a scalar's internal representation, implemented per curve. Read it
+/// through [`view_tag_from_scalar`] rather than directly.
 pub trait RawFr {
     type Fr;
     fn as_u64(&self) -> u64;
 }
 
+/// Rejects ephemeral private keys that leave the shared secret public. With 0 the ephemeral
+/// public key and the shared point are the identity, and with 1 the shared point is the
+/// recipient's viewing public key itself, so anyone could link the stealth address to the
+/// recipient. An identity shared point is also rejected on the recipient side, since decoding
+/// refuses identity ephemeral keys.
+pub fn validate_ephemeral_private_key<F: PrimeField>(
+    ephemeral_private_key: &F,
+) -> Result<(), StealthError> {
+    if ephemeral_private_key.is_zero() || ephemeral_private_key.is_one() {
+        return Err(StealthError::DegenerateEphemeralKey);
+    }
+    Ok(())
+}
+
+/// The compressed affine `x || y` of a point, empty for the identity.
+fn coordinate_bytes<G: CurveGroup>(point: &G) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Some((x, y)) = point.into_affine().xy() {
+        x.serialize_compressed(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+        y.serialize_compressed(&mut bytes)
+            .expect("serializing into a Vec cannot fail");
+    }
+    bytes
+}
+
+/// The exact bytes [`StealthAddressOnCurve::hash_to_fr`] hashes into the shared secret, for
+/// circuits, contracts and other implementations that must reproduce it: the ASCII display string
+/// of the affine shared point, `(x, y)` with both coordinates in decimal. Projective and affine
+/// points display the same, so either may be passed.
+pub fn shared_secret_hash_preimage<G: Display>(shared_point: &G) -> Vec<u8> {
+    shared_point.to_string().into_bytes()
+}
+
+/// Adds the spending key and the hashed shared secret into the stealth private key.
+///
+/// The sum must not leak the spending key through timing. Arkworks field addition runs the same
+/// instructions for every operand apart from its final conditional subtraction of the modulus,
+/// which the compiler usually lowers to a branch-free select. Every stealth key derivation goes
+/// through this one function, so a backend with a guaranteed constant-time reduction has to be
+/// swapped in only here.
+pub fn constant_time_add<F: PrimeField>(spending_key: F, shared_secret: F) -> F {
+    spending_key + shared_secret
+}
+
 pub trait StealthAddressOnCurve {
     type Projective: Display
         + Add<Output = Self::Projective>
-        + Mul<Self::Fr, Output = Self::Projective>;
+        + Mul<Self::Fr, Output = Self::Projective>
+        + CurveGroup<ScalarField = Self::Fr>;
     type Affine: AffineWrapper;
     type Fr: Add<Self::Fr, Output = Self::Fr> + ark_ff::PrimeField + RawFr;
 
+    const SCHEME: Scheme;
+
     fn derive_public_key(private_key: &Self::Fr) -> Self::Projective;
 
+    /// Checks that the generator behind [`StealthAddressOnCurve::derive_public_key`] is a nonzero
+    /// point on the curve and in the prime order subgroup.
+    fn validate_generator() -> Result<(), StealthError> {
+        validate_public_key(&Self::derive_public_key(&Self::Fr::one()))
+    }
+
+    #[cfg(feature = "os_rng")]
     fn random_keypair() -> (Self::Fr, Self::Projective) {
-        let private_key = Self::generate_random_fr();
+        Self::random_keypair_with(&mut OsRng)
+    }
+    fn random_keypair_with<R: Rng + ?Sized>(rng: &mut R) -> (Self::Fr, Self::Projective) {
+        let private_key = Self::generate_random_fr_with(rng);
         let public_key = Self::derive_public_key(&private_key);
         (private_key, public_key)
     }
+    #[cfg(feature = "os_rng")]
     fn generate_random_fr() -> Self::Fr {
-        let mut rng = OsRng;
-        Self::Fr::rand(&mut rng)
+        Self::generate_random_fr_with(&mut OsRng)
+    }
+    fn generate_random_fr_with<R: Rng + ?Sized>(rng: &mut R) -> Self::Fr {
+        Self::Fr::rand(rng)
     }
+    /// Hashes arbitrary bytes to a scalar. Empty input, which no protocol step produces, is
+    /// defined as [`crate::scheme::hash_to_fr_with_domain`] of no input under
+    /// [`crate::scheme::EMPTY_INPUT_DOMAIN`], a non-empty preimage, and the resulting constant is
+    /// pinned in each curve's tests so a dependency upgrade cannot change it silently.
+    /// Implementations must special case it before hashing.
     fn hash_to_fr(input: &[u8]) -> Self::Fr;
+    /// Hashes a point by its affine coordinates rather than its display string. Defaults to
+    /// [`StealthAddressOnCurve::hash_to_fr`] over the compressed `x || y`; curves with an
+    /// arithmetic hash override this to hash the coordinates as field elements.
+    fn hash_coordinates_to_fr(point: &Self::Projective) -> Self::Fr {
+        Self::hash_to_fr(&coordinate_bytes(point))
+    }
+    /// [`StealthAddressOnCurve::hash_coordinates_to_fr`] under `domain`, so that hashes of the
+    /// same point in different contexts never coincide. Defaults to
+    /// [`crate::scheme::hash_to_fr_with_domain`] over the compressed `x || y`; curves with an
+    /// arithmetic hash override this to hash the domain as one more field element.
+    fn hash_coordinates_to_fr_with_domain(domain: &[u8], point: &Self::Projective) -> Self::Fr
+    where
+        Self: Sized,
+    {
+        crate::scheme::hash_to_fr_with_domain::<Self>(domain, &coordinate_bytes(point))
+    }
+    /// Decodes the uncompressed public key encoding used on this curve's chains, for
+    /// [`crate::serialization::deserialize_public_key_any`]. Defaults to the arkworks
+    /// uncompressed form; curves with an EVM precompile encoding override this.
+    fn deserialize_public_key_uncompressed(bytes: &[u8]) -> Result<Self::Projective, StealthError> {
+        let point = Self::Projective::deserialize_uncompressed(bytes)?;
+        if point.is_zero() {
+            return Err(StealthError::IdentityPoint);
+        }
+        Ok(point)
+    }
     fn compute_shared_point(
         private_key: Self::Fr,
         public_key: Self::Projective,
@@ -43,13 +147,95 @@ pub trait StealthAddressOnCurve {
         viewing_public_key: Self::Projective,
         spending_public_key: Self::Projective,
         ephemeral_private_key: Self::Fr,
-    ) -> (Self::Projective, u64) {
+    ) -> Result<(Self::Projective, u64), StealthError> {
+        validate_ephemeral_private_key(&ephemeral_private_key)?;
         let q = Self::compute_shared_point(ephemeral_private_key, viewing_public_key);
-        let inputs = q.to_string();
-        let q_hashed = Self::hash_to_fr(inputs.as_bytes());
+        if q.is_zero() {
+            return Err(StealthError::IdentityPoint);
+        }
+        let q_hashed = Self::hash_to_fr(&shared_secret_hash_preimage(&q));
+
+        Ok(Self::commitment_from_hashed_secret(
+            q_hashed,
+            spending_public_key,
+        ))
+    }
+
+    /// [`StealthAddressOnCurve::generate_stealth_commitment`] with the commitment normalized to
+    /// affine coordinates, for zk provers working in affine form.
+    fn generate_stealth_commitment_affine(
+        viewing_public_key: Self::Projective,
+        spending_public_key: Self::Projective,
+        ephemeral_private_key: Self::Fr,
+    ) -> Result<(<Self::Projective as CurveGroup>::Affine, u64), StealthError> {
+        let (stealth_commitment, view_tag) = Self::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )?;
+        Ok((stealth_commitment.into_affine(), view_tag))
+    }
+
+    /// [`StealthAddressOnCurve::generate_stealth_commitment`] offset by `tweak * G`, for
+    /// protocols that agree a per-payment tweak of the spending key out of band. The view tag is
+    /// unchanged, so only a recipient that knows the tweak can recover the stealth key, with
+    /// [`StealthAddressOnCurve::generate_stealth_private_key_tweaked`].
+    fn generate_stealth_commitment_tweaked(
+        viewing_public_key: Self::Projective,
+        spending_public_key: Self::Projective,
+        ephemeral_private_key: Self::Fr,
+        tweak: Self::Fr,
+    ) -> Result<(Self::Projective, u64), StealthError> {
+        let (stealth_commitment, view_tag) = Self::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )?;
+        Ok((
+            stealth_commitment + Self::derive_public_key(&tweak),
+            view_tag,
+        ))
+    }
+
+    /// The stealth key for [`StealthAddressOnCurve::generate_stealth_commitment_tweaked`]: the
+    /// untweaked stealth key plus `tweak`. As with the untweaked key, only the view tag is
+    /// checked, so a wrong tweak is only caught by comparing the key with the commitment.
+    fn generate_stealth_private_key_tweaked(
+        ephemeral_public_key: Self::Projective,
+        viewing_key: Self::Fr,
+        spending_key: Self::Fr,
+        expected_view_tag: u64,
+        tweak: Self::Fr,
+    ) -> Option<Self::Fr> {
+        let stealth_private_key = Self::generate_stealth_private_key(
+            ephemeral_public_key,
+            viewing_key,
+            spending_key,
+            expected_view_tag,
+        )?;
+        Some(constant_time_add(stealth_private_key, tweak))
+    }
 
+    /// The hashed shared secret of a private key and the other party's public key, the same for
+    /// the sender's `(ephemeral_private_key, viewing_public_key)` and the recipient's
+    /// `(viewing_key, ephemeral_public_key)`.
+    fn derive_shared_secret_scalar(
+        private_key: Self::Fr,
+        public_key: Self::Projective,
+    ) -> Self::Fr {
+        let shared_point = Self::compute_shared_point(private_key, public_key);
+        Self::hash_to_fr(&shared_secret_hash_preimage(&shared_point))
+    }
+
+    /// The stealth commitment and view tag for an already hashed shared secret, for zk circuits
+    /// that compute the hash themselves. It skips the ephemeral key and shared point checks of
+    /// [`StealthAddressOnCurve::generate_stealth_commitment`].
+    fn commitment_from_hashed_secret(
+        q_hashed: Self::Fr,
+        spending_public_key: Self::Projective,
+    ) -> (Self::Projective, u64) {
         let q_hashed_in_g1 = Self::derive_public_key(&q_hashed);
-        let view_tag = q_hashed.as_u64();
+        let view_tag = view_tag_from_scalar(&q_hashed);
         (q_hashed_in_g1 + spending_public_key, view_tag)
     }
 
@@ -59,18 +245,197 @@ pub trait StealthAddressOnCurve {
         spending_key: Self::Fr,
         expected_view_tag: u64,
     ) -> Option<Self::Fr> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "generate_stealth_private_key",
+            tag_matched = tracing::field::Empty
+        )
+        .entered();
         let q_receiver = Self::compute_shared_point(viewing_key, ephemeral_public_key);
+        // an identity shared point can only come from malformed data
+        if q_receiver.is_zero() {
+            return None;
+        }
 
-        let inputs_receiver = q_receiver.to_string();
-        let q_receiver_hashed = Self::hash_to_fr(inputs_receiver.as_bytes());
+        let q_receiver_hashed = Self::hash_to_fr(&shared_secret_hash_preimage(&q_receiver));
 
         // Check if retrieved view tag matches the expected view tag
-        let view_tag = q_receiver_hashed.as_u64();
+        let view_tag = view_tag_from_scalar(&q_receiver_hashed);
+        #[cfg(feature = "tracing")]
+        span.record("tag_matched", view_tag == expected_view_tag);
         if view_tag == expected_view_tag {
-            let stealth_private_key = spending_key + q_receiver_hashed;
+            let stealth_private_key = constant_time_add(spending_key, q_receiver_hashed);
             Some(stealth_private_key)
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Curve = crate::TestCurve;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    #[test]
+    fn test_constant_time_add() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (shared_secret, _) = Curve::random_keypair();
+        let minus_one = -Fr::one();
+        for (a, b) in [
+            (spending_key, shared_secret),
+            (Fr::zero(), Fr::zero()),
+            (minus_one, Fr::one()),
+            (minus_one, minus_one),
+        ] {
+            assert_eq!(constant_time_add(a, b), a + b);
+        }
+
+        // the derived stealth key still controls the stealth commitment
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let (stealth_commitment, view_tag) = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        let stealth_private_key = Curve::generate_stealth_private_key(
+            ephemeral_public_key,
+            viewing_key,
+            spending_key,
+            view_tag,
+        )
+        .unwrap();
+        assert_eq!(
+            Curve::derive_public_key(&stealth_private_key),
+            stealth_commitment
+        );
+    }
+
+    #[test]
+    fn test_commitment_from_hashed_secret() {
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+
+        let q_hashed =
+            Curve::derive_shared_secret_scalar(ephemeral_private_key, viewing_public_key);
+        assert_eq!(
+            Curve::derive_shared_secret_scalar(viewing_key, ephemeral_public_key),
+            q_hashed
+        );
+        assert_eq!(
+            Curve::commitment_from_hashed_secret(q_hashed, spending_public_key),
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_shared_secret_hash_preimage() {
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+
+        let shared_point = Curve::compute_shared_point(viewing_key, ephemeral_public_key);
+        let preimage = shared_secret_hash_preimage(&shared_point);
+        let affine = shared_point.into_affine();
+        let (x, y) = affine.xy().unwrap();
+        assert_eq!(preimage, format!("({x}, {y})").into_bytes());
+
+        let q_hashed = Curve::hash_to_fr(&preimage);
+        assert_eq!(
+            Curve::derive_shared_secret_scalar(ephemeral_private_key, viewing_public_key),
+            q_hashed
+        );
+        assert_eq!(
+            Curve::commitment_from_hashed_secret(q_hashed, spending_public_key),
+            Curve::generate_stealth_commitment(
+                viewing_public_key,
+                spending_public_key,
+                ephemeral_private_key
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_stealth_commitment_affine() {
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+
+        let (affine, view_tag) = Curve::generate_stealth_commitment_affine(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        let (projective, expected_view_tag) = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert_eq!(affine.xy(), projective.into_affine().xy());
+        assert_eq!(view_tag, expected_view_tag);
+        assert_eq!(
+            Curve::generate_stealth_commitment_affine(
+                viewing_public_key,
+                spending_public_key,
+                Fr::one()
+            ),
+            Err(StealthError::DegenerateEphemeralKey)
+        );
+    }
+
+    #[test]
+    fn test_tweaked_commitment() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (ephemeral_private_key, ephemeral_public_key) = Curve::random_keypair();
+        let (tweak, _) = Curve::random_keypair();
+
+        let (stealth_commitment, view_tag) = Curve::generate_stealth_commitment_tweaked(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+            tweak,
+        )
+        .unwrap();
+        let (untweaked, untweaked_view_tag) = Curve::generate_stealth_commitment(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert_ne!(stealth_commitment, untweaked);
+        assert_eq!(view_tag, untweaked_view_tag);
+
+        let recover = |tweak| {
+            Curve::generate_stealth_private_key_tweaked(
+                ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                view_tag,
+                tweak,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            Curve::derive_public_key(&recover(tweak)),
+            stealth_commitment
+        );
+        assert_ne!(
+            Curve::derive_public_key(&recover(tweak + Fr::one())),
+            stealth_commitment
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! A symmetric key from the same ECDH as the stealth address, so a sender can encrypt a memo
+//! that only the recipient's viewing key opens.
+//!
+//! The key is derived from the shared point with a [`SymmetricKdf`], by default HKDF-SHA256 with
+//! [`SYMMETRIC_KEY_DOMAIN`] as info. Every KDF is domain separated from the hashed shared secret
+//! that the stealth address is derived from, so the memo key never reveals it. The free functions
+//! here use the default KDF; [`crate::scheme::StealthScheme::with_symmetric_kdf`] selects another.
+
+use crate::announcement::Announcement;
+use crate::scan::tag_checked_shared_secret;
+use crate::serialization::{serialize_fr, serialize_point};
+use crate::stealth_commitments::{constant_time_add, StealthAddressOnCurve};
+use ark_ec::CurveGroup;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+pub const SYMMETRIC_KEY_DOMAIN: &[u8] = b"erc-5564-symmetric-key";
+
+/// The key derivation applied to the shared point. Sender and recipient must use the same one to
+/// agree on the key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymmetricKdf {
+    /// HKDF-SHA256 (RFC 5869) of the compressed shared point, with an empty salt and
+    /// [`SYMMETRIC_KEY_DOMAIN`] as info.
+    #[default]
+    HkdfSha256,
+    /// [`StealthAddressOnCurve::hash_coordinates_to_fr_with_domain`] of the shared point under
+    /// [`SYMMETRIC_KEY_DOMAIN`], as a little-endian scalar, i.e.
+    /// `poseidon_hash([hash_to_field(domain), x, y])` on bn254, for circuits that decrypt
+    /// in-circuit. The bls curves have no poseidon instance and fall back to keccak over the
+    /// domain and coordinates.
+    Poseidon,
+}
+
+impl SymmetricKdf {
+    pub fn derive_key<C: StealthAddressOnCurve>(&self, shared_point: &C::Projective) -> [u8; 32] {
+        let mut key = [0; 32];
+        match self {
+            SymmetricKdf::HkdfSha256 => {
+                Hkdf::<Sha256>::new(None, &serialize_point(shared_point))
+                    .expand(SYMMETRIC_KEY_DOMAIN, &mut key)
+                    .expect("32 bytes is a valid HKDF-SHA256 output length");
+            }
+            SymmetricKdf::Poseidon => {
+                key.copy_from_slice(&serialize_fr(&C::hash_coordinates_to_fr_with_domain(
+                    SYMMETRIC_KEY_DOMAIN,
+                    shared_point,
+                )));
+            }
+        }
+        key
+    }
+}
+
+fn derive_symmetric_key<C: StealthAddressOnCurve>(shared_point: &C::Projective) -> [u8; 32] {
+    SymmetricKdf::default().derive_key::<C>(shared_point)
+}
+
+/// The sender's symmetric key for an announcement made with `ephemeral_private_key`.
+pub fn symmetric_shared_key<C: StealthAddressOnCurve>(
+    ephemeral_private_key: C::Fr,
+    viewing_public_key: C::Projective,
+) -> [u8; 32] {
+    derive_symmetric_key::<C>(&C::compute_shared_point(
+        ephemeral_private_key,
+        viewing_public_key,
+    ))
+}
+
+/// The recipient's side of [`symmetric_shared_key`], from the announcement's ephemeral public key.
+pub fn symmetric_shared_key_recipient<C: StealthAddressOnCurve>(
+    viewing_key: C::Fr,
+    ephemeral_public_key: C::Projective,
+) -> [u8; 32] {
+    derive_symmetric_key::<C>(&C::compute_shared_point(viewing_key, ephemeral_public_key))
+}
+
+/// Like [`crate::scan::recover`], but also returns the [`symmetric_shared_key_recipient`] for
+/// the announcement, derived from the same shared point so it is only computed once.
+pub fn recover_full<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+    spending_key: C::Fr,
+) -> Option<(C::Fr, [u8; 32])> {
+    let shared_point = C::compute_shared_point(viewing_key, announcement.ephemeral_public_key);
+    let q_hashed = tag_checked_shared_secret(announcement, &shared_point.into_affine())?;
+    let stealth_private_key = constant_time_add(spending_key, q_hashed);
+    if C::derive_public_key(&stealth_private_key) != announcement.stealth_commitment {
+        return None;
+    }
+    Some((
+        stealth_private_key,
+        derive_symmetric_key::<C>(&shared_point),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::announcement::generate_announcement;
+    use crate::scan::recover;
+
+    type Curve = crate::TestCurve;
+
+    #[test]
+    fn test_sender_and_recipient_agree() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+        assert!(recover(&announcement, viewing_key, spending_key).is_some());
+
+        let key = symmetric_shared_key::<Curve>(ephemeral_private_key, viewing_public_key);
+        assert_eq!(
+            symmetric_shared_key_recipient::<Curve>(viewing_key, announcement.ephemeral_public_key),
+            key
+        );
+        assert_ne!(
+            symmetric_shared_key_recipient::<Curve>(other_key, announcement.ephemeral_public_key),
+            key
+        );
+    }
+
+    #[test]
+    fn test_recover_full() {
+        let (spending_key, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let (ephemeral_private_key, _) = Curve::random_keypair();
+        let announcement = generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            ephemeral_private_key,
+        )
+        .unwrap();
+
+        let (stealth_private_key, key) =
+            recover_full(&announcement, viewing_key, spending_key).unwrap();
+        assert_eq!(
+            key,
+            symmetric_shared_key::<Curve>(ephemeral_private_key, viewing_public_key)
+        );
+        assert_eq!(
+            Some(stealth_private_key),
+            Curve::generate_stealth_private_key(
+                announcement.ephemeral_public_key,
+                viewing_key,
+                spending_key,
+                announcement.view_tag
+            )
+        );
+        assert_eq!(recover_full(&announcement, other_key, spending_key), None);
+        assert_eq!(recover_full(&announcement, viewing_key, other_key), None);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::announcement::Announcement;
+use crate::serialization::{deserialize_public_key, serialize_point};
+use crate::stealth_commitments::{RawFr, StealthAddressOnCurve};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_serialize::CanonicalSerialize;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Number of bytes in a view tag.
+pub const VIEW_TAG_BYTE_WIDTH: usize = 8;
+
+/// The view tag of a hashed shared secret: the least significant 64-bit limb of the scalar in
+/// Montgomery form, i.e. `(scalar * 2^(64 * limbs) mod p) mod 2^64` with `limbs` the number of
+/// 64-bit limbs of the modulus. Only [`RawFr`] implementations read the internal representation;
+/// everything else derives tags through this function.
+pub fn view_tag_from_scalar<F: RawFr>(scalar: &F) -> u64 {
+    scalar.as_u64()
+}
+
+/// The view tag a contract can reproduce from the shared point: the low 64 bits of
+/// `keccak256(X || Y)`, read from the digest as a big-endian `uint256`. `X` and `Y` are the
+/// big-endian affine coordinates, each as wide as the base field, so on bn254 `X || Y` is the 64
+/// byte precompile encoding and the tag is `uint64(uint256(keccak256(abi.encodePacked(x, y))))`.
+/// The identity has no coordinates and hashes as the empty string.
+pub fn evm_view_tag<G: CurveGroup>(shared_point: &G) -> u64 {
+    let mut input = Vec::new();
+    if let Some((x, y)) = shared_point.into_affine().xy() {
+        for coordinate in [x, y] {
+            let mut bytes = Vec::new();
+            coordinate
+                .serialize_compressed(&mut bytes)
+                .expect("serializing into a Vec cannot fail");
+            bytes.reverse();
+            input.extend(bytes);
+        }
+    }
+    let mut hash = [0; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(&input);
+    hasher.finalize(&mut hash);
+    u64::from_be_bytes(hash[24..].try_into().unwrap())
+}
+
+/// Probability that a view tag of `tag_byte_width` bytes matches for an announcement that was not
+/// addressed to the scanner, i.e. the share of announcements that need the full commitment check.
+pub fn expected_false_positive_rate(tag_byte_width: usize) -> f64 {
+    256f64.powi(-(tag_byte_width as i32))
+}
+
+/// Expected fraction of all recipients whose view tag an announcement's `view_tag_width` byte tag
+/// matches, i.e. how far publishing the tag narrows the announcement's candidate recipients.
+/// This is the same `1 / 256^width` as [`expected_false_positive_rate`], seen from the sender's
+/// side.
+pub fn anonymity_reduction(view_tag_width: usize) -> f64 {
+    expected_false_positive_rate(view_tag_width)
+}
+
+/// Whether `tag_byte_width` is a width a view tag can be truncated to, from one byte up to
+/// [`VIEW_TAG_BYTE_WIDTH`].
+pub fn is_valid_view_tag_width(tag_byte_width: usize) -> bool {
+    (1..=VIEW_TAG_BYTE_WIDTH).contains(&tag_byte_width)
+}
+
+/// Mask selecting the low `tag_byte_width` bytes of a view tag.
+pub fn view_tag_mask(tag_byte_width: usize) -> u64 {
+    if tag_byte_width >= VIEW_TAG_BYTE_WIDTH {
+        u64::MAX
+    } else {
+        (1u64 << (8 * tag_byte_width)) - 1
+    }
+}
+
+/// Counts announcements whose first view tag byte is `tag`, for measuring the one byte tag's
+/// false positive rate empirically against [`expected_false_positive_rate`]`(1)`.
+pub fn count_tag_matches<C: StealthAddressOnCurve>(
+    announcements: &[Announcement<C>],
+    tag: u8,
+) -> usize {
+    announcements
+        .iter()
+        .filter(|announcement| announcement.view_tag.to_le_bytes()[0] == tag)
+        .count()
+}
+
+/// How often each value of the first view tag byte occurs across `announcements`, for studying
+/// how well the announced tags hide which announcements a recipient scans.
+pub fn tag_histogram<C: StealthAddressOnCurve>(announcements: &[Announcement<C>]) -> [u32; 256] {
+    let mut histogram = [0; 256];
+    for announcement in announcements {
+        histogram[usize::from(announcement.view_tag.to_le_bytes()[0])] += 1;
+    }
+    histogram
+}
+
+/// Debug check that `announcement` carries the view tag `viewing_key` derives from its ephemeral
+/// public key after a serialization round trip of that key, i.e. that encoding the key cannot
+/// change which tag the recipient computes.
+///
+/// # Panics
+///
+/// If the ephemeral public key does not round trip, or the recomputed tag, masked to the
+/// announcement's view tag width, differs from the stored one.
+pub fn assert_tag_stable<C: StealthAddressOnCurve>(
+    announcement: &Announcement<C>,
+    viewing_key: C::Fr,
+) {
+    let ephemeral_public_key = deserialize_public_key::<C::Projective>(&serialize_point(
+        &announcement.ephemeral_public_key,
+    ))
+    .expect("ephemeral public key must round trip");
+    assert_eq!(ephemeral_public_key, announcement.ephemeral_public_key);
+    let shared_secret = C::derive_shared_secret_scalar(viewing_key, ephemeral_public_key);
+    assert_eq!(
+        view_tag_from_scalar(&shared_secret) & view_tag_mask(announcement.view_tag_width),
+        announcement.view_tag,
+        "view tag is not stable across serialization"
+    );
+}
+
+/// The first view tag byte of every announcement, stored contiguously so the first scanning pass
+/// runs over a tight byte array the compiler can vectorize instead of strided announcements.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ViewTagIndex {
+    tags: Vec<u8>,
+}
+
+impl ViewTagIndex {
+    pub fn new<C: StealthAddressOnCurve>(announcements: &[Announcement<C>]) -> Self {
+        ViewTagIndex {
+            tags: announcements
+                .iter()
+                .map(|announcement| announcement.view_tag.to_le_bytes()[0])
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Indices of the announcements whose first view tag byte is `expected`. These still need
+    /// the full tag and commitment checks.
+    pub fn candidates(&self, expected: u8) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        for (chunk_index, chunk) in self.tags.chunks(64).enumerate() {
+            // branch-free over the chunk so the comparison vectorizes
+            let mut mask = chunk.iter().enumerate().fold(0u64, |mask, (i, tag)| {
+                mask | (u64::from(*tag == expected) << i)
+            });
+            while mask != 0 {
+                candidates.push(chunk_index * 64 + mask.trailing_zeros() as usize);
+                mask &= mask - 1;
+            }
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{BigInteger, Field, PrimeField};
+    use ark_std::rand::{thread_rng, Rng};
+
+    type Curve = crate::TestCurve;
+    type Fr = <Curve as StealthAddressOnCurve>::Fr;
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "bls12_381")] {
+            const KNOWN_TAG: u64 = 0x2ffd_ffff_d002;
+        } else if #[cfg(feature = "bls12_377")] {
+            const KNOWN_TAG: u64 = 0x6496_ffff_fffe_d5fa;
+        } else if #[cfg(feature = "bn254")] {
+            const KNOWN_TAG: u64 = 0x15fa_4ca7_9fff_8d06;
+        }
+    }
+
+    // the low 64 bits of keccak256 of the generator's big-endian coordinates; on bn254 that is
+    // keccak256(abi.encodePacked(uint256(1), uint256(2))), 0xe90b7bce...44d599ccd2a7c2e0
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "bls12_381")] {
+            const KNOWN_EVM_TAG: u64 = 0x8bea_6bd8_8261_9afe;
+        } else if #[cfg(feature = "bls12_377")] {
+            const KNOWN_EVM_TAG: u64 = 0x7655_b8cf_c63d_0466;
+        } else if #[cfg(feature = "bn254")] {
+            const KNOWN_EVM_TAG: u64 = 0x44d5_99cc_d2a7_c2e0;
+        }
+    }
+
+    #[test]
+    fn test_evm_view_tag_is_pinned() {
+        let generator = Curve::derive_public_key(&Fr::from(1u64));
+        assert_eq!(evm_view_tag(&generator), KNOWN_EVM_TAG);
+        assert_ne!(evm_view_tag(&(generator + generator)), KNOWN_EVM_TAG);
+    }
+
+    #[test]
+    fn test_view_tag_from_scalar() {
+        assert_eq!(view_tag_from_scalar(&Fr::from(5564u64)), KNOWN_TAG);
+
+        let limbs = <Fr as PrimeField>::BigInt::NUM_LIMBS as u64;
+        let montgomery_radix = Fr::from(2u64).pow([64 * limbs]);
+        for scalar in [
+            Fr::from(0u64),
+            Fr::from(1u64),
+            -Fr::from(1u64),
+            Curve::random_keypair().0,
+        ] {
+            assert_eq!(
+                view_tag_from_scalar(&scalar),
+                (scalar * montgomery_radix).into_bigint().as_ref()[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_expected_false_positive_rate() {
+        assert_eq!(expected_false_positive_rate(0), 1.0);
+        assert_eq!(expected_false_positive_rate(1), 1.0 / 256.0);
+        assert_eq!(expected_false_positive_rate(2), 1.0 / 65536.0);
+    }
+
+    #[test]
+    fn test_anonymity_reduction() {
+        assert!((anonymity_reduction(1) - 0.0039).abs() < 1e-4);
+        assert!((anonymity_reduction(2) - 1.5e-5).abs() < 1e-6);
+        assert_eq!(anonymity_reduction(0), 1.0);
+    }
+
+    #[test]
+    fn test_count_tag_matches_within_tolerance() {
+        let (_, stealth_commitment) = Curve::random_keypair();
+        let (_, ephemeral_public_key) = Curve::random_keypair();
+        let mut rng = thread_rng();
+        // only the tags matter here, and view tags are uniformly distributed
+        let count = 25_600;
+        let announcements: Vec<Announcement<Curve>> = (0..count)
+            .map(|_| Announcement::new(stealth_commitment, ephemeral_public_key, rng.gen()))
+            .collect();
+        let tag: u8 = rng.gen();
+
+        let expected = count as f64 * expected_false_positive_rate(1);
+        let matches = count_tag_matches(&announcements, tag) as f64;
+        assert!((matches - expected).abs() < 50.0, "{matches} vs {expected}");
+
+        assert_eq!(
+            count_tag_matches(&announcements, tag),
+            tag_histogram(&announcements)[usize::from(tag)] as usize
+        );
+    }
+
+    #[test]
+    fn test_view_tag_index_candidates() {
+        let (_, public_key) = Curve::random_keypair();
+        let mut rng = thread_rng();
+        let announcements: Vec<Announcement<Curve>> = (0..1000)
+            .map(|_| Announcement::new(public_key, public_key, rng.gen::<u64>() & 0x1ff))
+            .collect();
+        let index = ViewTagIndex::new(&announcements);
+        assert_eq!(index.len(), announcements.len());
+
+        for expected in [0u8, 1, 0x7f, 0xff] {
+            let reference: Vec<usize> = announcements
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| a.view_tag as u8 == expected)
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(index.candidates(expected), reference);
+        }
+        assert!(ViewTagIndex::new::<Curve>(&[]).candidates(0).is_empty());
+    }
+
+    #[test]
+    fn test_tag_histogram() {
+        let (_, public_key) = Curve::random_keypair();
+        // the tag byte is the low byte of the u64, higher bytes are ignored
+        let announcements: Vec<Announcement<Curve>> = [(0x00, 3), (0x1_07, 5), (0xff, 1)]
+            .into_iter()
+            .flat_map(|(tag, count)| {
+                std::iter::repeat_n(Announcement::new(public_key, public_key, tag), count)
+            })
+            .collect();
+
+        let histogram = tag_histogram(&announcements);
+        assert_eq!(histogram[0x00], 3);
+        assert_eq!(histogram[0x07], 5);
+        assert_eq!(histogram[0xff], 1);
+        assert_eq!(histogram.iter().sum::<u32>(), 9);
+        assert_eq!(tag_histogram::<Curve>(&[]), [0; 256]);
+    }
+
+    #[test]
+    fn test_serialization_preserves_view_tag() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (viewing_key, viewing_public_key) = Curve::random_keypair();
+        for _ in 0..16 {
+            let announcement = crate::announcement::generate_announcement::<Curve>(
+                viewing_public_key,
+                spending_public_key,
+                Curve::random_keypair().0,
+            )
+            .unwrap();
+            assert_tag_stable(&announcement, viewing_key);
+            let decoded = Announcement::<Curve>::from_bytes(&announcement.to_bytes()).unwrap();
+            assert_eq!(decoded.view_tag, announcement.view_tag);
+            assert_tag_stable(&decoded, viewing_key);
+            assert_tag_stable(&announcement.with_view_tag_width(1), viewing_key);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "view tag is not stable")]
+    fn test_assert_tag_stable_rejects_other_viewing_key() {
+        let (_, spending_public_key) = Curve::random_keypair();
+        let (_, viewing_public_key) = Curve::random_keypair();
+        let (other_key, _) = Curve::random_keypair();
+        let announcement = crate::announcement::generate_announcement::<Curve>(
+            viewing_public_key,
+            spending_public_key,
+            Curve::random_keypair().0,
+        )
+        .unwrap();
+        assert_tag_stable(&announcement, other_key);
+    }
+}
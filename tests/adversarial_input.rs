@@ -0,0 +1,181 @@
+//! Throws malformed public input at every function that accepts external bytes or points. None
+//! of them may panic; each must reject the input through its `Result` or `Option`.
+
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::CanonicalSerialize;
+use erc_5564_rs::announcement::Announcement;
+use erc_5564_rs::error::StealthError;
+use erc_5564_rs::meta_address::StealthMetaAddress;
+use erc_5564_rs::scan::{recover, scan_announcements};
+use erc_5564_rs::serialization::{
+    compute_shared_point_bytes, deserialize_fr, deserialize_public_key, public_key_size,
+    scalar_size, serialize_fr, serialize_public_key,
+};
+use erc_5564_rs::stealth_commitments::StealthAddressOnCurve;
+use num_traits::Zero;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "bls12_381")] {
+        type Curve = ark_bls12_381::Bls12_381;
+    } else if #[cfg(feature = "bls12_377")] {
+        type Curve = ark_bls12_377::Bls12_377;
+    } else if #[cfg(feature = "bn254")] {
+        type Curve = ark_bn254::Bn254;
+    }
+}
+
+type Fr = <Curve as StealthAddressOnCurve>::Fr;
+type Projective = <Curve as StealthAddressOnCurve>::Projective;
+
+fn identity_bytes() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    Projective::zero().serialize_compressed(&mut bytes).unwrap();
+    bytes
+}
+
+fn malformed_points() -> Vec<Vec<u8>> {
+    let size = public_key_size::<Projective>();
+    let mut points = vec![
+        vec![],
+        vec![0xff; size - 1],
+        vec![0xff; size + 1],
+        vec![0xff; size],
+        identity_bytes(),
+    ];
+    // small x coordinates, roughly half of which are not on the curve
+    points.extend((0..=255u8).map(|x| {
+        let mut bytes = vec![0; size];
+        bytes[0] = x;
+        bytes
+    }));
+    points
+}
+
+fn malformed_scalars() -> Vec<Vec<u8>> {
+    let size = scalar_size::<Fr>();
+    vec![
+        vec![],
+        vec![1; size - 1],
+        vec![1; size + 1],
+        Fr::MODULUS.to_bytes_le(),
+        vec![0xff; size],
+    ]
+}
+
+#[test]
+fn test_deserializers_reject_malformed_input() {
+    assert_eq!(
+        deserialize_public_key::<Projective>(&identity_bytes()),
+        Err(StealthError::IdentityPoint)
+    );
+    let mut off_curve = 0;
+    for bytes in malformed_points() {
+        match deserialize_public_key::<Projective>(&bytes) {
+            Ok(point) => assert!(!point.is_zero()),
+            Err(_) => off_curve += 1,
+        }
+    }
+    assert!(off_curve > 5);
+
+    let (_, public_key) = Curve::random_keypair();
+    let public_key = serialize_public_key(&public_key).unwrap();
+    for bytes in malformed_points() {
+        if deserialize_public_key::<Projective>(&bytes).is_err() {
+            let meta_address = [public_key.as_slice(), bytes.as_slice()].concat();
+            assert!(StealthMetaAddress::<Curve>::from_bytes(&meta_address).is_err());
+        }
+    }
+
+    for bytes in malformed_scalars() {
+        assert!(deserialize_fr::<Fr>(&bytes).is_err());
+    }
+}
+
+#[test]
+fn test_shared_point_rejects_malformed_input() {
+    let (private_key, public_key) = Curve::random_keypair();
+    let private_key = serialize_fr(&private_key);
+    let public_key = serialize_public_key(&public_key).unwrap();
+
+    for bytes in malformed_scalars() {
+        assert!(compute_shared_point_bytes::<Curve>(&bytes, &public_key).is_err());
+    }
+    for bytes in malformed_points() {
+        if deserialize_public_key::<Projective>(&bytes).is_err() {
+            assert!(compute_shared_point_bytes::<Curve>(&private_key, &bytes).is_err());
+        }
+    }
+}
+
+#[test]
+fn test_announcement_rejects_malformed_input() {
+    let size = Announcement::<Curve>::encoded_size();
+    let valid = Announcement::<Curve>::new(Curve::random_keypair().1, Curve::random_keypair().1, 0)
+        .to_bytes();
+
+    for bytes in [vec![], vec![0xff], valid[..size - 1].to_vec(), {
+        let mut too_long = valid.clone();
+        too_long.push(0);
+        too_long
+    }] {
+        assert!(Announcement::<Curve>::from_bytes(&bytes).is_err());
+    }
+
+    let point_size = public_key_size::<Projective>();
+    for point in malformed_points()
+        .into_iter()
+        .filter(|point| point.len() == point_size)
+    {
+        if deserialize_public_key::<Projective>(&point).is_ok() {
+            continue;
+        }
+        let mut bytes = valid.clone();
+        bytes[1..1 + point_size].copy_from_slice(&point);
+        assert!(Announcement::<Curve>::from_bytes(&bytes).is_err());
+
+        let mut bytes = valid.clone();
+        bytes[1 + point_size..1 + 2 * point_size].copy_from_slice(&point);
+        assert!(Announcement::<Curve>::from_bytes(&bytes).is_err());
+    }
+}
+
+#[test]
+fn test_scanning_survives_degenerate_announcements() {
+    let (spending_key, _) = Curve::random_keypair();
+    let (viewing_key, _) = Curve::random_keypair();
+    let (_, public_key) = Curve::random_keypair();
+
+    // announcements built in memory skip the decoding checks, so scanning must cope on its own
+    let announcements = [
+        Announcement::<Curve>::new(Projective::zero(), Projective::zero(), 0),
+        Announcement::<Curve>::new(public_key, Projective::zero(), u64::MAX),
+        Announcement::<Curve>::new(Projective::zero(), public_key, 0),
+    ];
+    for announcement in &announcements {
+        assert_eq!(recover(announcement, viewing_key, spending_key), None);
+        assert_eq!(recover(announcement, Fr::zero(), Fr::zero()), None);
+    }
+    assert!(scan_announcements(&announcements, viewing_key, spending_key).is_empty());
+}
+
+#[cfg(feature = "bn254")]
+#[test]
+fn test_uncompressed_rejects_malformed_input() {
+    use erc_5564_rs::bn254_impl::deserialize_public_key_uncompressed;
+
+    assert_eq!(
+        deserialize_public_key_uncompressed(&[0; 64]),
+        Err(StealthError::IdentityPoint)
+    );
+    for bytes in [[0xff; 64], [1; 64], {
+        let mut off_curve = [0; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 1;
+        off_curve
+    }] {
+        assert_eq!(
+            deserialize_public_key_uncompressed(&bytes),
+            Err(StealthError::InvalidEncoding)
+        );
+    }
+}